@@ -227,6 +227,8 @@ Stimulus.register("astromap", class extends Controller {
       .then(data => {
         // Store volumes - already in hash format with zero-padded keys
         this.volumes = data["volumes"] || {};
+        // Jump graph exported by the Rust generator (--jump-graph); null for older JSON
+        this.jumpGraph = data["jump_graph"] || null;
         console.log("Loaded data. Volume count:", Object.keys(this.volumes).length);
         
         if (this.coordinatesTarget.value.length == 4) {
@@ -305,6 +307,8 @@ Stimulus.register("astromap", class extends Controller {
   
   routableVolumes(key) {
     if (key.length != 4) { return; }
+    // A graph built to less than jump-3 lacks some neighbours; compute them instead.
+    if (this.jumpGraph && this.jumpGraph.max_jump >= 3) { return this.graphRoutableVolumes(key); }
     var routes = {}
     var x = parseInt(key.slice(0, 2))
     var y = parseInt(key.slice(2))
@@ -356,6 +360,25 @@ Stimulus.register("astromap", class extends Controller {
     this.routableVolumesTarget.innerHTML = result;
   }
   
+  // Neighbours within jump-3 straight from the exported jump graph, nearest first.
+  graphRoutableVolumes(key) {
+    var result = "";
+    this.jumpGraph.edges
+      .filter(edge => edge.jump <= 3 && (edge.from == key || edge.to == key))
+      .sort((a, b) => a.jump - b.jump)
+      .forEach(edge => {
+        var coord = edge.from == key ? edge.to : edge.from;
+        var node = this.jumpGraph.nodes.find(n => n.hex == coord) || {};
+        result += this.ROUTE_TEMPLATE.template({
+          coord: coord,
+          distance: edge.jump,
+          name: node.name || '',
+          uwp: node.uwp || ''
+        });
+      });
+    this.routableVolumesTarget.innerHTML = result;
+  }
+
   setCoordinate(event) {
     event.preventDefault();
     this.coordinatesTarget.value = event.target.innerHTML;
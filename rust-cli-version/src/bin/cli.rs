@@ -1,6 +1,13 @@
 use astromapper_core::config::{self, Config};
-use astromapper_core::formatters::{AsciiFormatter, JsonFormatter, SvgGenerator};
+use astromapper_core::formatters::{AsciiFormatter, JsonFormatter, RouteMode, SvgGenerator};
 use astromapper_core::rules::{runtime, Ruleset};
+use astromapper_core::models::sector::{hex_jump, parse_hex};
+use astromapper_core::models::Sector;
+use astromapper_core::navigation::{
+    build_network, find_mains, find_route, mark_way_stations, travellermap_metadata, JumpGraph,
+    RouteOptions,
+};
+use astromapper_core::trade::{check_date, speculate, trade_routes, traffic};
 use astromapper_core::{generate_crawford_seed, generate_sector, generate_volume, string_to_crawford};
 use chrono::Local;
 use clap::Parser;
@@ -25,10 +32,10 @@ struct Args {
     /// Name for the sector
     #[arg(long)]
     name: Option<String>,
-    /// Ruleset: t5, cepheus, or a custom rules/<name>.yml
+    /// Ruleset: t5, cepheus, mgt2, classic, or a custom rules/<name>.yml
     #[arg(long)]
     ruleset: Option<String>,
-    /// Stellar realism: firm | normal | opera
+    /// Stellar realism: firm | normal | opera, or a genre from the ruleset/config
     #[arg(long)]
     genre: Option<String>,
     /// Native life: 'human' or 'varied'
@@ -49,6 +56,33 @@ struct Args {
     /// Island border opacity, 0.0-1.0
     #[arg(long)]
     island_opacity: Option<f64>,
+    /// Compute trade routes (WTN/BTN) and draw them on the SVG
+    #[arg(long)]
+    trade_routes: Option<bool>,
+    /// Farthest trading pair in jumps (0 = the ruleset's trade.max_jump)
+    #[arg(long)]
+    trade_jump: Option<i64>,
+    /// SVG jump lines: legacy (slope buckets) | spanning | trade (BTN-weighted)
+    #[arg(long)]
+    route_mode: Option<String>,
+    /// Longest jump drawn by the spanning/trade route modes
+    #[arg(long)]
+    route_jump: Option<i64>,
+    /// Outline T5 mains (jump-1 chains) on the SVG
+    #[arg(long)]
+    mains: Option<bool>,
+    /// Minimum systems per main
+    #[arg(long)]
+    main_min: Option<usize>,
+    /// Build the X-boat network (SVG layer, JSON, TravellerMap route metadata)
+    #[arg(long)]
+    xboat: Option<bool>,
+    /// Build the jump graph up to this jump into the JSON, .dot and .graphml (0 = off)
+    #[arg(long)]
+    jump_graph: Option<i64>,
+    /// Thrust in G for the jump-point travel times in the .txt
+    #[arg(long)]
+    thrust: Option<f64>,
     /// YAML config file (default: _astromapper.yml; flags override it)
     #[arg(long)]
     config: Option<String>,
@@ -57,6 +91,106 @@ struct Args {
     list_densities: bool,
 }
 
+/// `astromapper trade` flags: price the goods one world sells to another.
+#[derive(Parser, Debug)]
+#[command(name = "astromapper trade")]
+#[command(about = "Quote speculative cargo from one hex of a generated sector to another")]
+struct TradeArgs {
+    /// Sector JSON written by a previous run (output/sector_*.json)
+    sector: String,
+    /// Source hex (CCRR)
+    from: String,
+    /// Destination hex (CCRR)
+    to: String,
+    /// Broker skill added to both price rolls
+    #[arg(long, default_value_t = 0)]
+    broker: i64,
+    /// Seed for the price rolls (default: the sector name)
+    #[arg(long)]
+    seed: Option<String>,
+    /// Ruleset whose goods table to use (default: the config's, else t5)
+    #[arg(long)]
+    ruleset: Option<String>,
+    /// YAML config file (default: _astromapper.yml)
+    #[arg(long)]
+    config: Option<String>,
+}
+
+/// `astromapper traffic` flags: passengers, freight and mail waiting for one jump.
+#[derive(Parser, Debug)]
+#[command(name = "astromapper traffic")]
+#[command(about = "Roll passenger, freight and mail traffic from one hex to another")]
+struct TrafficArgs {
+    /// Sector JSON written by a previous run (output/sector_*.json)
+    sector: String,
+    /// Source hex (CCRR)
+    from: String,
+    /// Destination hex (CCRR)
+    to: String,
+    /// In-game date (DDD-YYYY); each date rolls fresh traffic
+    #[arg(long, default_value = "001-1105")]
+    date: String,
+    /// Seed for the traffic rolls (default: the sector name)
+    #[arg(long)]
+    seed: Option<String>,
+    /// Ruleset whose traffic tables to use (default: the config's, else t5)
+    #[arg(long)]
+    ruleset: Option<String>,
+    /// YAML config file (default: _astromapper.yml)
+    #[arg(long)]
+    config: Option<String>,
+}
+
+/// `astromapper route` flags: the shortest itinerary for a ship of a given jump.
+#[derive(Parser, Debug)]
+#[command(name = "astromapper route")]
+#[command(about = "Plan a jump route between two hexes, refuelling only where fuel is available")]
+struct RouteArgs {
+    /// Source hex (CCRR)
+    from: String,
+    /// Destination hex (CCRR)
+    to: String,
+    /// Ship's jump rating
+    #[arg(long, default_value_t = 2)]
+    jump: i64,
+    /// Hull displacement in tons (sizes the fuel bill)
+    #[arg(long, default_value_t = 200)]
+    hull: i64,
+    /// Never stop at an Amber-zone world
+    #[arg(long)]
+    avoid_amber: bool,
+    /// Never stop at a Red-zone world
+    #[arg(long)]
+    avoid_red: bool,
+    /// Prefer stops with class A or B starports
+    #[arg(long)]
+    prefer_ab: bool,
+    /// Sector JSON (default: the newest output/sector_*.json)
+    #[arg(long)]
+    sector: Option<String>,
+    /// Ruleset whose fuel prices to use (default: the config's, else t5)
+    #[arg(long)]
+    ruleset: Option<String>,
+    /// YAML config file (default: _astromapper.yml)
+    #[arg(long)]
+    config: Option<String>,
+}
+
+/// `astromapper rules` flags: print a merged ruleset with where each value came from.
+#[derive(Parser, Debug)]
+#[command(name = "astromapper rules")]
+#[command(about = "Print a ruleset's merged values, each with the file that supplied it")]
+struct RulesArgs {
+    /// Ruleset to show (default: the config's, else t5)
+    ruleset: Option<String>,
+    /// Only values at or below this dotted path (e.g. starport, uwp.size)
+    #[arg(long)]
+    path: Option<String>,
+    /// YAML config file (default: _astromapper.yml)
+    #[arg(long)]
+    config: Option<String>,
+}
+
 fn density_value(name: &str) -> Option<f64> {
     Some(match name {
         "extra-galactic" => 0.01,
@@ -77,6 +211,22 @@ fn main() -> anyhow::Result<()> {
     if raw.len() >= 2 && raw[1] == "new" {
         return run_new(&raw[2..]);
     }
+    // Subcommand: `astromapper trade <sector.json> <from> <to>` quotes speculative cargo.
+    if raw.len() >= 2 && raw[1] == "trade" {
+        return run_trade(TradeArgs::parse_from(&raw[1..]));
+    }
+    // Subcommand: `astromapper traffic <sector.json> <from> <to>` rolls a week's traffic.
+    if raw.len() >= 2 && raw[1] == "traffic" {
+        return run_traffic(TrafficArgs::parse_from(&raw[1..]));
+    }
+    // Subcommand: `astromapper route <from> <to> --jump N` plans a refuelling itinerary.
+    if raw.len() >= 2 && raw[1] == "route" {
+        return run_route(RouteArgs::parse_from(&raw[1..]));
+    }
+    // Subcommand: `astromapper rules [name]` shows which file set each ruleset value.
+    if raw.len() >= 2 && raw[1] == "rules" {
+        return run_rules(RulesArgs::parse_from(&raw[1..]));
+    }
 
     let args = Args::parse();
 
@@ -110,6 +260,16 @@ fn main() -> anyhow::Result<()> {
     let island_jump = args.island_jump.unwrap_or(cfg.island_jump);
     let island_min = args.island_min.unwrap_or(cfg.island_min);
     let island_opacity = args.island_opacity.unwrap_or(cfg.island_opacity);
+    let trade = args.trade_routes.unwrap_or(cfg.trade_routes);
+    let trade_jump = args.trade_jump.unwrap_or(cfg.trade_jump);
+    let route_mode_name = args.route_mode.unwrap_or(cfg.route_mode);
+    let route_jump = args.route_jump.unwrap_or(cfg.route_jump);
+    let route_styles: Vec<(String, String)> = cfg.route_styles.into_iter().collect();
+    let mains = args.mains.unwrap_or(cfg.mains);
+    let main_min = args.main_min.unwrap_or(cfg.main_min);
+    let xboat = args.xboat.unwrap_or(cfg.xboat);
+    let graph_jump = args.jump_graph.unwrap_or(cfg.jump_graph);
+    let thrust = args.thrust.unwrap_or(cfg.thrust);
     let seed_arg = args.seed.or_else(|| (!cfg.seed.is_empty()).then(|| cfg.seed.clone()));
 
     let Some(density) = density_value(&density_name) else {
@@ -118,6 +278,17 @@ fn main() -> anyhow::Result<()> {
         std::process::exit(1);
     };
 
+    let Some(route_mode) = RouteMode::from_name(&route_mode_name) else {
+        eprintln!("Error: Invalid route mode '{}' (legacy | spanning | trade)", route_mode_name);
+        std::process::exit(1);
+    };
+
+    // Travel times divide by the thrust.
+    if thrust.is_nan() || thrust <= 0.0 {
+        eprintln!("Error: Invalid thrust '{}' (must be above 0 G)", thrust);
+        std::process::exit(1);
+    }
+
     // Seed generation/conversion.
     let (seed, crawford_code) = if let Some(s) = seed_arg {
         let crawford = string_to_crawford(&s);
@@ -142,6 +313,18 @@ fn main() -> anyhow::Result<()> {
             std::process::exit(1);
         }
     };
+    let rs = match with_config_genres(rs, cfg.genres) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error in {}'s `genres:`: {}", cfg_path, e);
+            std::process::exit(1);
+        }
+    };
+    let genre = genre.to_lowercase();
+    if !rs.genres().contains(&genre) {
+        eprintln!("Error: Unknown genre '{}' (known: {})", genre, rs.genres().join(", "));
+        std::process::exit(1);
+    }
     let ruleset_title = rs.title();
     println!("Ruleset: {}  Genre: {}", ruleset_title, genre);
     runtime::set_ruleset(rs);
@@ -163,10 +346,28 @@ fn main() -> anyhow::Result<()> {
                 sector.prune_isolated(4);
             }
             sector.ruleset_title = ruleset_title.clone();
+            sector.thrust = thrust;
+            sector.mains = find_mains(&sector, main_min);
+            if xboat {
+                let net = build_network(&sector);
+                mark_way_stations(&mut sector, &net);
+                sector.xboat = Some(net);
+            }
+            if trade {
+                let jump = if trade_jump > 0 { trade_jump } else { runtime::ruleset().trade_max_jump() };
+                sector.trade_routes = trade_routes(&sector, jump);
+            }
+            if graph_jump > 0 {
+                sector.jump_graph = Some(JumpGraph::build(&sector, graph_jump));
+            }
 
             let ascii_content = AsciiFormatter::format_sector(&sector);
             let svg_content = SvgGenerator::new(sector.name.clone())
                 .with_islands(islands, island_jump, island_min, island_opacity)
+                .with_trade_routes(trade)
+                .with_mains(mains)
+                .with_routes(route_mode, route_jump)
+                .with_route_styles(route_styles)
                 .generate(&sector);
             let json_content = JsonFormatter::format_sector(&sector)?;
             let tab_content = sector.to_tab("");
@@ -177,17 +378,30 @@ fn main() -> anyhow::Result<()> {
             fs::write(format!("{base}.svg"), svg_content)?;
             fs::write(format!("{base}.json"), json_content)?;
             fs::write(format!("{base}.tab"), tab_content)?;
+            if let Some(net) = &sector.xboat {
+                fs::write(format!("{base}.xml"), travellermap_metadata(&sector.name, net))?;
+            }
+            if let Some(graph) = &sector.jump_graph {
+                fs::write(format!("{base}.dot"), graph.to_dot(&sector.name))?;
+                fs::write(format!("{base}.graphml"), graph.to_graphml(&sector.name))?;
+            }
 
             println!("ASCII saved to: {base}.txt");
             println!("SVG saved to:   {base}.svg");
             println!("JSON saved to:  {base}.json");
             println!("TAB saved to:   {base}.tab");
+            if sector.xboat.is_some() {
+                println!("X-boat routes:  {base}.xml");
+            }
+            if sector.jump_graph.is_some() {
+                println!("Graph saved to: {base}.dot, {base}.graphml");
+            }
             println!("Generated {} star systems in sector", sector.system_count());
         }
         "volume" => {
             println!("Generating single volume...");
             let volume = generate_volume(seed.clone(), 0, 0)?;
-            let ascii_content = AsciiFormatter::format_volume(&volume);
+            let ascii_content = volume.to_ascii_at(thrust);
             let json_content = JsonFormatter::format_volume(&volume)?;
 
             let timestamp = Local::now().format("%Y%m%d-%H%M%S");
@@ -213,6 +427,185 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Load a saved sector and print the goods the `from` world stocks, priced at both
+/// ends of the run.
+fn run_trade(args: TradeArgs) -> anyhow::Result<()> {
+    let sector = load_saved_sector(&args.sector, args.ruleset, args.config)?;
+    let world = |hex: &str| {
+        sector
+            .world_at(hex)
+            .ok_or_else(|| anyhow::anyhow!("no world at hex {:?} in {}", hex, args.sector))
+    };
+    let (src, dst) = (world(&args.from)?, world(&args.to)?);
+    let seed = args.seed.unwrap_or_else(|| sector.name.clone());
+    let quotes = speculate(src, dst, &args.from, &args.to, args.broker, &seed);
+
+    println!(
+        "{} ({}) {} -> {} ({}) {}",
+        src.name, args.from, src.trade_codes_string(),
+        dst.name, args.to, dst.trade_codes_string()
+    );
+    if quotes.is_empty() {
+        println!("Nothing for sale.");
+        return Ok(());
+    }
+    println!(
+        "{:<28} {:>5} {:>4} {:>4} {:>10} {:>10} {:>10}",
+        "Good", "Tons", "PDM", "SDM", "Buy Cr/t", "Sell Cr/t", "Profit/t"
+    );
+    for q in &quotes {
+        println!(
+            "{:<28} {:>5} {:>+4} {:>+4} {:>10} {:>10} {:>+10}",
+            q.name, q.tons, q.purchase_dm, q.sale_dm, q.purchase_price, q.sale_price,
+            q.profit_per_ton()
+        );
+    }
+    Ok(())
+}
+
+/// Roll and print the traffic waiting at `from` for `to` on the given date.
+fn run_traffic(args: TrafficArgs) -> anyhow::Result<()> {
+    check_date(&args.date).map_err(|e| anyhow::anyhow!(e))?;
+    let sector = load_saved_sector(&args.sector, args.ruleset, args.config)?;
+    let world = |hex: &str| {
+        let (col, row) =
+            parse_hex(hex).ok_or_else(|| anyhow::anyhow!("bad hex {:?} (want CCRR)", hex))?;
+        let w = sector
+            .world_at(hex)
+            .ok_or_else(|| anyhow::anyhow!("no world at hex {:?} in {}", hex, args.sector))?;
+        anyhow::Ok((col, row, w))
+    };
+    let ((c1, r1, src), (c2, r2, dst)) = (world(&args.from)?, world(&args.to)?);
+    let seed = args.seed.unwrap_or_else(|| sector.name.clone());
+    let jump = hex_jump(c1, r1, c2, r2);
+    let t = traffic(src, dst, jump, (&args.from, &args.to), &seed, &args.date);
+
+    println!(
+        "{} ({}) -> {} ({}), jump-{}, {}",
+        src.name, args.from, dst.name, args.to, jump, args.date
+    );
+    let pax: Vec<String> = t.passengers.iter().map(|(c, n)| format!("{n} {c}")).collect();
+    println!("Passengers: {}", if pax.is_empty() { "none".into() } else { pax.join(", ") });
+    if t.freight.is_empty() {
+        println!("Freight:    none");
+    } else {
+        let mut classes: Vec<(&str, usize, i64)> = Vec::new();
+        for lot in &t.freight {
+            match classes.iter_mut().find(|(c, _, _)| *c == lot.class) {
+                Some(entry) => {
+                    entry.1 += 1;
+                    entry.2 += lot.tons;
+                }
+                None => classes.push((&lot.class, 1, lot.tons)),
+            }
+        }
+        let lots: Vec<String> =
+            classes.iter().map(|(c, n, tons)| format!("{n} {c} ({tons}t)")).collect();
+        println!("Freight:    {}t in {}", t.freight_tons(), lots.join(", "));
+    }
+    println!("Mail:       {} container(s)", t.mail);
+    Ok(())
+}
+
+/// Plan and print a jump itinerary.
+fn run_route(args: RouteArgs) -> anyhow::Result<()> {
+    let path = match args.sector {
+        Some(p) => p,
+        None => newest_sector_json()?,
+    };
+    let sector = load_saved_sector(&path, args.ruleset, args.config)?;
+    let opts = RouteOptions {
+        jump: args.jump,
+        hull: args.hull,
+        avoid_amber: args.avoid_amber,
+        avoid_red: args.avoid_red,
+        prefer_ab: args.prefer_ab,
+    };
+    let it = find_route(&sector, &args.from, &args.to, &opts).map_err(|e| anyhow::anyhow!(e))?;
+    let name = |hex: &str| {
+        let v = parse_hex(hex).and_then(|(c, r)| sector.get_volume((r - 1) as usize, (c - 1) as usize));
+        v.map(|v| v.world.as_ref().map_or(&v.name, |w| &w.name).clone()).unwrap_or_default()
+    };
+
+    println!("Jump-{} route {} -> {} ({})", args.jump, args.from, args.to, path);
+    for (i, leg) in it.legs.iter().enumerate() {
+        println!(
+            "{:>3}. {} {:<14} -> {} {:<14} J{}  {:>4}t {:<10} Cr{}",
+            i + 1, leg.from, name(&leg.from), leg.to, name(&leg.to), leg.parsecs, leg.tons,
+            leg.fuel, leg.cost
+        );
+    }
+    println!(
+        "{} jump(s), {} parsecs, {} week(s); fuel {}t, Cr{}",
+        it.legs.len(), it.parsecs, it.weeks, it.fuel_tons, it.fuel_cost
+    );
+    Ok(())
+}
+
+/// Print every merged value of a ruleset (or those under `--path`) with the file
+/// that supplied it after `extends:`/`include:`; `overlay` marks config genres.
+fn run_rules(args: RulesArgs) -> anyhow::Result<()> {
+    let cfg_path = args.config.unwrap_or_else(|| "_astromapper.yml".into());
+    let (cfg, _) = Config::load(&cfg_path).map_err(|e| anyhow::anyhow!(e))?;
+    let name = args.ruleset.unwrap_or(cfg.ruleset);
+    let rs = Ruleset::load(&name, ".").map_err(|e| anyhow::anyhow!("loading ruleset {:?}: {}", name, e))?;
+    let rs = with_config_genres(rs, cfg.genres)
+        .map_err(|e| anyhow::anyhow!("{}'s `genres:`: {}", cfg_path, e))?;
+    let origins = rs.origins(args.path.as_deref().unwrap_or(""));
+    if origins.is_empty() {
+        anyhow::bail!("ruleset {:?} has no values under {:?}", name, args.path.unwrap_or_default());
+    }
+    let width = origins.iter().map(|(p, _)| p.len()).max().unwrap_or(0);
+    for (path, file) in origins {
+        let value = rs.value_at(path).map(|v| serde_json::to_string(v).unwrap_or_default());
+        println!("{:<width$}  {}  # {}", path, value.unwrap_or_default(), file);
+    }
+    Ok(())
+}
+
+/// The ruleset with the config's `genres:` merged over it (unchanged when none).
+fn with_config_genres(rs: Ruleset, genres: serde_yaml::Mapping) -> Result<Ruleset, String> {
+    if genres.is_empty() {
+        return Ok(rs);
+    }
+    let mut extra = serde_yaml::Mapping::new();
+    extra.insert("genres".into(), serde_yaml::Value::Mapping(genres));
+    rs.overlay(serde_yaml::Value::Mapping(extra))
+}
+
+/// The most recently written output/sector_*.json.
+fn newest_sector_json() -> anyhow::Result<String> {
+    let mut found: Vec<(std::time::SystemTime, String)> = Vec::new();
+    for entry in fs::read_dir("output")? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.starts_with("sector_") && name.ends_with(".json") {
+            found.push((fs::metadata(&path)?.modified()?, path.display().to_string()));
+        }
+    }
+    found
+        .into_iter()
+        .max()
+        .map(|(_, p)| p)
+        .ok_or_else(|| anyhow::anyhow!("no output/sector_*.json; generate a sector or pass --sector"))
+}
+
+/// Load the ruleset (flag, else config, else t5) into the runtime and read back a
+/// sector JSON from a previous run.
+fn load_saved_sector(
+    path: &str,
+    ruleset: Option<String>,
+    config: Option<String>,
+) -> anyhow::Result<Sector> {
+    let cfg_path = config.unwrap_or_else(|| "_astromapper.yml".into());
+    let (cfg, _) = Config::load(&cfg_path).map_err(|e| anyhow::anyhow!(e))?;
+    let ruleset_name = ruleset.unwrap_or(cfg.ruleset);
+    let rs = Ruleset::load(&ruleset_name, ".")
+        .map_err(|e| anyhow::anyhow!("loading ruleset {:?}: {}", ruleset_name, e))?;
+    runtime::set_ruleset(rs);
+    Ok(JsonFormatter::parse_sector(&fs::read_to_string(path)?)?)
+}
+
 /// Scaffold a project directory (like Ruby's `astromapper new <name>`): the dir, an
 /// _astromapper.yml seeded with the name, and an output/ folder.
 fn run_new(args: &[String]) -> anyhow::Result<()> {
@@ -3,7 +3,7 @@ use crate::models::orbit::{
     EmptyOrbit, WorldOrbit, GasGiant, Belt, Hostile, Rockball,
     GiantSize, Moon
 };
-use crate::rng;
+use crate::rules::{runtime, Context, OrbitModule, T5Module, Value};
 use crate::error::Result;
 use crate::builders::WorldBuilder;
 use crate::data::get_planet_names;
@@ -12,246 +12,135 @@ pub struct OrbitBuilder;
 
 impl OrbitBuilder {
     pub fn populate_orbits(star: &mut Star) -> Result<()> {
-        // Calculate number of orbits
-        let dm = Self::orbit_dm(star);
-        let num_orbits = (rng::roll_2d6().unwrap_or(7) as i32 + dm).max(0) as u8;
-        
+        // Orbit count, zone tables and moons come from the ruleset's `orbits:`.
+        let rs = runtime::ruleset();
+        let (star_type, class) = (star.star_type.to_string(), star.star_size.to_string());
+        let num_orbits = rs.orbit_count(&star_type, &class).clamp(0, u8::MAX as i64) as u8;
+
         let names = get_planet_names();
         let mut world_found = false;
-        
+        let mut ctx = Context::new();
+        ctx.insert("type".to_string(), Value::Str(star_type));
+        ctx.insert("class".to_string(), Value::Str(class));
+
         for orbit_num in 0..num_orbits {
             let au = star.orbit_to_au(orbit_num);
-            
+
             // Skip if beyond outer limit
             if au > star.outer_limit() {
                 break;
             }
-            
-            let orbit = Self::populate_orbit(star, orbit_num, au, &names, &mut world_found)?;
+
+            ctx.insert("orbit".to_string(), Value::Int(orbit_num as i64));
+            let orbit = Self::populate_orbit(star, orbit_num, au, &names, &mut world_found, &ctx)?;
             star.orbits.push(orbit);
         }
-        
+
         Ok(())
     }
-    
-    fn orbit_dm(star: &Star) -> i32 {
-        let mut dm = 0;
-        
-        // Size modifiers
-        match star.star_size {
-            crate::models::StarSize::Ia | crate::models::StarSize::Ib => dm -= 4,
-            crate::models::StarSize::II => dm -= 2,
-            crate::models::StarSize::III => dm += 4,
-            crate::models::StarSize::IV | crate::models::StarSize::V => dm += 0,
-            crate::models::StarSize::VI | crate::models::StarSize::D => dm += 8,
-        }
-        
-        // Type modifiers
-        match star.star_type {
-            crate::models::StarType::M => dm -= 4,
-            crate::models::StarType::K => dm -= 2,
-            _ => {}
-        }
-        
-        dm
-    }
-    
+
     fn populate_orbit(
         star: &Star,
         orbit_num: u8,
         au: f64,
         names: &[String],
         world_found: &mut bool,
+        ctx: &Context,
     ) -> Result<OrbitContent> {
         // Check if in inner limit
         if au < star.inner_limit() {
             return Ok(OrbitContent::Empty(EmptyOrbit { orbit_number: orbit_num, au }));
         }
-        
+
         let (bio_inner, bio_outer) = star.biozone();
-        
+
         // Determine zone
         let zone = if au < bio_inner {
-            -1 // Inner zone
+            "inner"
         } else if au > bio_outer {
-            1  // Outer zone
+            "outer"
         } else {
-            0  // Biozone
-        };
-        
-        // Generate content based on zone
-        let content = match zone {
-            0 => {
-                // Biozone - try to place world if not found yet
-                if !*world_found && rng::roll_2d6().unwrap_or(7) >= 4 {
-                    *world_found = true;
-                    let world = WorldBuilder::new(0, 0)
-                        .with_names(names.to_vec())
-                        .with_orbit(orbit_num)
-                        .with_star_type(star.star_type)
-                        .build()?;
-                    OrbitContent::World(WorldOrbit { 
-                        orbit_number: orbit_num, 
-                        au, 
-                        world 
-                    })
-                } else {
-                    Self::generate_non_world_orbit(orbit_num, au, zone)
-                }
-            }
-            -1 => {
-                // Inner zone
-                Self::generate_inner_orbit(orbit_num, au)
-            }
-            1 => {
-                // Outer zone
-                Self::generate_outer_orbit(orbit_num, au)
-            }
-            _ => OrbitContent::Empty(EmptyOrbit { orbit_number: orbit_num, au }),
+            "habitable"
         };
-        
-        Ok(content)
-    }
-    
-    fn generate_inner_orbit(orbit_num: u8, au: f64) -> OrbitContent {
-        let roll = rng::roll_2d6().unwrap_or(7);
-        
-        match roll {
-            2..=4 => OrbitContent::Empty(EmptyOrbit { orbit_number: orbit_num, au }),
-            5..=6 => OrbitContent::Rockball(Rockball { orbit_number: orbit_num, au }),
-            7..=9 => {
-                // Hostile world
-                let atmosphere = rng::roll_1d6() + 9; // Corrosive/insidious
-                let hydrographics = rng::roll_1d6() - 1;
-                OrbitContent::Hostile(Hostile { 
-                    orbit_number: orbit_num, 
-                    au,
-                    atmosphere: atmosphere.min(15) as u8,
-                    hydrographics: hydrographics.max(0) as u8,
-                })
-            }
-            10..=11 => OrbitContent::Belt(Belt { orbit_number: orbit_num, au }),
-            _ => {
-                // Small gas giant
-                let moons = Self::generate_moons();
-                OrbitContent::GasGiant(GasGiant {
-                    orbit_number: orbit_num,
-                    au,
-                    size: GiantSize::Small,
-                    moons,
-                })
-            }
-        }
-    }
-    
-    fn generate_outer_orbit(orbit_num: u8, au: f64) -> OrbitContent {
-        let roll = rng::roll_2d6().unwrap_or(7);
-        
-        match roll {
-            2..=3 => OrbitContent::Empty(EmptyOrbit { orbit_number: orbit_num, au }),
-            4..=5 => OrbitContent::Belt(Belt { orbit_number: orbit_num, au }),
-            6..=7 => OrbitContent::Rockball(Rockball { orbit_number: orbit_num, au }),
-            8..=9 => {
-                // Hostile frozen world
-                let atmosphere = rng::roll_1d6() - 1;
-                let hydrographics = 0; // Frozen
-                OrbitContent::Hostile(Hostile { 
-                    orbit_number: orbit_num, 
-                    au,
-                    atmosphere: atmosphere.max(0) as u8,
-                    hydrographics,
-                })
-            }
-            10 => {
-                // Small gas giant
-                let moons = Self::generate_moons();
-                OrbitContent::GasGiant(GasGiant {
-                    orbit_number: orbit_num,
-                    au,
-                    size: GiantSize::Small,
-                    moons,
-                })
-            }
-            _ => {
-                // Large gas giant
-                let moons = Self::generate_moons();
-                OrbitContent::GasGiant(GasGiant {
-                    orbit_number: orbit_num,
-                    au,
-                    size: GiantSize::Large,
-                    moons,
-                })
-            }
+
+        // Biozone - try to place world if not found yet
+        if zone == "habitable" && !*world_found && runtime::ruleset().orbit_has_mainworld(ctx) {
+            *world_found = true;
+            let world = WorldBuilder::new(0, 0)
+                .with_names(names.to_vec())
+                .with_orbit(orbit_num)
+                .with_star_type(star.star_type)
+                .build()?;
+            return Ok(OrbitContent::World(WorldOrbit {
+                orbit_number: orbit_num,
+                au,
+                world,
+            }));
         }
+
+        Ok(Self::generate_orbit(orbit_num, au, zone, ctx))
     }
-    
-    fn generate_non_world_orbit(orbit_num: u8, au: f64, _zone: i32) -> OrbitContent {
-        let roll = rng::roll_2d6().unwrap_or(7);
-        
-        match roll {
-            2..=4 => OrbitContent::Empty(EmptyOrbit { orbit_number: orbit_num, au }),
-            5..=6 => OrbitContent::Belt(Belt { orbit_number: orbit_num, au }),
-            7..=8 => OrbitContent::Rockball(Rockball { orbit_number: orbit_num, au }),
-            _ => {
-                // Gas giant
-                let size = if rng::roll_1d6() >= 4 {
-                    GiantSize::Large
-                } else {
-                    GiantSize::Small
-                };
-                let moons = Self::generate_moons();
-                OrbitContent::GasGiant(GasGiant {
+
+    fn generate_orbit(orbit_num: u8, au: f64, zone: &str, ctx: &Context) -> OrbitContent {
+        let rs = runtime::ruleset();
+        let giant = |size| {
+            OrbitContent::GasGiant(GasGiant {
+                orbit_number: orbit_num,
+                au,
+                size,
+                moons: Self::generate_moons(),
+            })
+        };
+
+        match rs.orbit_body(zone, ctx).as_str() {
+            "rockball" => OrbitContent::Rockball(Rockball { orbit_number: orbit_num, au }),
+            "belt" => OrbitContent::Belt(Belt { orbit_number: orbit_num, au }),
+            "hostile" => {
+                let atmosphere = rs.orbit_value(&format!("zones/{zone}/hostile/atmo"), ctx);
+                let hydrographics = rs.orbit_value(&format!("zones/{zone}/hostile/hydro"), ctx);
+                OrbitContent::Hostile(Hostile {
                     orbit_number: orbit_num,
                     au,
-                    size,
-                    moons,
+                    atmosphere: atmosphere.clamp(0, 15) as u8,
+                    hydrographics: hydrographics.clamp(0, 10) as u8,
                 })
             }
+            "small_giant" => giant(GiantSize::Small),
+            "large_giant" => giant(GiantSize::Large),
+            _ => OrbitContent::Empty(EmptyOrbit { orbit_number: orbit_num, au }),
         }
     }
-    
+
     fn generate_moons() -> Vec<Moon> {
-        // Orbit tables from Ruby code
-        let close_orbits = vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
-        let ring_orbits = vec![1u8, 1, 1, 2, 2, 3];
-        let far_orbits: Vec<u8> = close_orbits.iter().map(|&x| (x as u16 * 5).min(255) as u8).collect();
-        let extreme_orbits: Vec<u8> = close_orbits.iter().map(|&x| (x as u16 * 25).min(255) as u8).collect();
-        
-        let num_moons = rng::roll_1d6();
+        let rs = runtime::ruleset();
+        let num_moons = rs.orbit_value("moons/count", &Context::new()).max(0);
         let mut moons = Vec::new();
-        
+
         for orbit in 0..num_moons {
-            let size = (rng::roll_1d6() as i8 - 3).max(0) as u8;
-            
-            // Calculate orbital radius based on dice rolls (Ruby logic)
-            let orbit_roll = rng::roll_2d6().unwrap_or(7) + orbit;
-            let orbital_radius = if size < 1 {
-                // Ring/small moon - use ring orbits table
-                let idx = ((rng::roll_1d6() - 1) as usize).min(ring_orbits.len() - 1);
-                ring_orbits[idx]
-            } else if orbit_roll == 12 {
-                // Extreme orbit (for gas giant moons)
-                let idx = (rng::roll_2d6().unwrap_or(7) as usize).min(extreme_orbits.len() - 1);
-                extreme_orbits[idx]
-            } else if orbit_roll < 8 {
-                // Close orbit
-                let idx = (rng::roll_2d6().unwrap_or(7) as usize).min(close_orbits.len() - 1);
-                close_orbits[idx]
-            } else {
-                // Far orbit
-                let idx = (rng::roll_2d6().unwrap_or(7) as usize).min(far_orbits.len() - 1);
-                far_orbits[idx]
-            };
-            
+            let mut ctx = Context::new();
+            ctx.insert("moon".to_string(), Value::Int(orbit));
+            let size = rs.orbit_value("moons/size", &ctx).max(0);
+            ctx.insert("size".to_string(), Value::Int(size));
+            let placement = rs.orbit_value("moons/orbit", &ctx);
+            ctx.insert("orbit".to_string(), Value::Int(placement));
+            let orbital_radius = rs.moon_radius(&ctx).clamp(0, u8::MAX as i64) as u8;
+
             moons.push(Moon {
                 orbit: orbit as u8,
                 orbital_radius,
-                size,
+                size: size.min(u8::MAX as i64) as u8,
                 atmosphere: 0,
                 hydrographics: 0,
             });
         }
-        
+
         moons
     }
-}
\ No newline at end of file
+}
+
+impl OrbitModule for T5Module {
+    /// The ruleset's `orbits:` tables.
+    fn populate_orbits(&self, star: &mut Star) -> Result<()> {
+        OrbitBuilder::populate_orbits(star)
+    }
+}
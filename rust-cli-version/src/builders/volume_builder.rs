@@ -16,6 +16,7 @@ impl VolumeBuilder {
     
     pub fn build(self) -> Result<Volume> {
         let mut volume = Volume::new(self.row, self.col);
+        rng::set_hex(&volume.coords());
         
         // A system exists wherever the sector's density roll placed this volume — the
         // density is the single gate (matching Ruby/Go); whether it has a mainworld
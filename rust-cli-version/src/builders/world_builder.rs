@@ -1,15 +1,14 @@
 //! World generation, converged onto Traveller 5 via the active ruleset (mirrors the
 //! Ruby orbit.rb World/Terrestrial and the Go pkg/builder/world.go). The UWP spine,
-//! trade codes, and starport/tech/base tables come from rules/<name>.yml; the
-//! algorithmic modules (climate, native, Ix/Ex/Cx) and the genre realism passes are
-//! code here.
+//! trade codes, starport/tech/base tables and genre realism passes come from
+//! rules/<name>.yml; the built-in climate, native and Ix/Ex/Cx modules are code here.
 
-use crate::error::Result;
-use crate::models::world::{Base, Temperature, World};
+use crate::error::{AstromapperError, Result};
+use crate::models::world::{Base, Culture, Facilities, Military, Temperature, World};
 use crate::models::StarType;
 use crate::rng;
 use crate::rules::runtime;
-use crate::rules::{Context, Value};
+use crate::rules::{ClimateModule, Context, ExtensionsModule, NativeModule, NoModule, T5Module, Value};
 
 pub struct WorldBuilder {
     row: usize,
@@ -67,22 +66,28 @@ impl WorldBuilder {
         world.hydrographics = hydro as u8;
 
         // Climate, then the genre realism pass (may thin atmosphere / dry hydro).
-        world.temperature = climate(self.orbit_number);
+        let climate = runtime::climate_module().map_err(AstromapperError::RulesetError)?;
+        world.temperature = climate.climate(&world, self.orbit_number)?;
         ctx.insert("temp".into(), Value::Str(world.temperature.to_code().to_string()));
-        apply_genre_atmo_hydro(&mut world, &genre);
-        ctx.insert("atmo".into(), Value::Int(world.atmosphere as i64));
-        ctx.insert("hydro".into(), Value::Int(world.hydrographics as i64));
-
-        // Population — the port-orientation roll is taken now (firm nudges it by pop).
-        let mut port_roll = rng::roll_2d6()? as i64;
-        let mut pop = rs.uwp_step("pop", &ctx);
-        let stripped = firm_pop_strip(&world, &genre, pop, port_roll);
-        pop = stripped.0;
-        port_roll = stripped.1;
+        let atmo = rs.genre_adjust(&genre, "atmo", &ctx, atmo);
+        ctx.insert("atmo".into(), Value::Int(atmo));
+        let hydro = rs.genre_adjust(&genre, "hydro", &ctx, hydro);
+        ctx.insert("hydro".into(), Value::Int(hydro));
+        world.atmosphere = atmo as u8;
+        world.hydrographics = hydro as u8;
+
+        // Population — the starport roll is taken now (a genre's `port_dm` nudges it
+        // by pop, the ruleset's `pop_dm` by the final population).
+        let mut port_roll = rs.starport_roll(&ctx);
+        let mut pop = rs.uwp_step("pop", &ctx) + rs.genre_dm(&genre, "pop_dm", &ctx);
+        let mut cp = ctx.clone();
+        cp.insert("pop".into(), Value::Int(pop));
+        port_roll = (port_roll + rs.genre_dm(&genre, "port_dm", &cp)).max(0);
         pop = pop.clamp(0, 15);
         pop = cap_colony_population(&world, pop, self.star_type);
         world.population = pop as u8;
         ctx.insert("pop".into(), Value::Int(pop));
+        port_roll += rs.starport_pop_dm(pop);
 
         world.government = rs.uwp_step("gov", &ctx) as u8;
         ctx.insert("gov".into(), Value::Int(world.government as i64));
@@ -106,7 +111,9 @@ impl WorldBuilder {
         ctx.insert("law".into(), Value::Int(world.law_level as i64));
 
         world.trade_codes = rs.trade_codes(&ctx);
-        world.bases = generate_bases(world.starport)?;
+        world.bases = generate_bases(&ctx, &world.trade_codes);
+        world.facilities = rng::with_stream("facilities", || starport_facilities(world.starport, &ctx));
+        world.wtn = rs.trade_wtn(&ctx);
 
         if world.population > 0 {
             world.pop_multiplier = 1 + rng::roll_range(9)? as u8;
@@ -135,11 +142,14 @@ impl WorldBuilder {
         if law_level > 9 {
             num = num.saturating_sub(1);
         }
-        let types = ["O", "O", "O", "O", "F", "F", "M", "M", "N", "N", "S", "S", "P"];
-        let rolls: Vec<usize> = (0..5).map(|_| rng::roll_2d6().unwrap_or(7) as usize).collect();
+        // Faction types come from the ruleset's `tables.factions`, indexed by 2D.
+        let rs = runtime::ruleset();
+        let rolls: Vec<i64> = (0..5).map(|_| rng::roll_2d6().unwrap_or(7) as i64).collect();
         let mut factions = Vec::new();
-        for i in 0..num.min(rolls.len()) {
-            factions.push(types[rolls[i].min(types.len() - 1)].to_string());
+        for &roll in &rolls[..num.min(rolls.len())] {
+            if let Some(Value::Str(kind)) = rs.table_entry("factions", roll) {
+                factions.push(kind);
+            }
         }
         Ok(factions)
     }
@@ -147,100 +157,56 @@ impl WorldBuilder {
 
 // ---- climate / native modules ------------------------------------------
 
-fn climate(orbit_number: u8) -> Temperature {
-    let module = runtime::ruleset().module_for("climate").unwrap_or_else(|_| "t5".into());
-    if module == "none" {
-        return Temperature::Temperate;
-    }
-    if orbit_number <= 1 {
-        return Temperature::Twilight;
-    }
-    let variance = [-2, -1, -1, -1, 0, 0, 0, 0, 0, 1, 1, 1, 2][(flux() + 6).clamp(0, 12) as usize];
-    if variance <= -1 {
-        Temperature::Hot
-    } else if variance >= 1 {
-        Temperature::Cold
-    } else {
-        Temperature::Temperate
+impl ClimateModule for T5Module {
+    /// Habitable-Zone variance (T5 page 432).
+    fn climate(&self, _world: &World, orbit_number: u8) -> Result<Temperature> {
+        if orbit_number <= 1 {
+            return Ok(Temperature::Twilight);
+        }
+        let variance = [-2, -1, -1, -1, 0, 0, 0, 0, 0, 1, 1, 1, 2][(flux() + 6).clamp(0, 12) as usize];
+        Ok(if variance <= -1 {
+            Temperature::Hot
+        } else if variance >= 1 {
+            Temperature::Cold
+        } else {
+            Temperature::Temperate
+        })
     }
 }
 
-fn native_status(w: &World) -> String {
-    let module = runtime::ruleset().module_for("native").unwrap_or_else(|_| "t5".into());
-    if module == "none" {
-        return String::new();
-    }
-    let varied = runtime::sophonts() == "varied";
-    if varied {
-        if w.population >= 7 {
-            return if w.atmosphere <= 1 { "Exotic".into() } else { "Native".into() };
-        }
-        if (1..=6).contains(&w.population) {
-            return "Colony".into();
-        }
-        return String::new();
-    }
-    if w.population >= 7 {
-        "Settled".into()
-    } else if (1..=6).contains(&w.population) {
-        "Colony".into()
-    } else {
-        String::new()
+impl ClimateModule for NoModule {
+    fn climate(&self, _world: &World, _orbit_number: u8) -> Result<Temperature> {
+        Ok(Temperature::Temperate)
     }
 }
 
-// ---- genre realism passes ----------------------------------------------
-
-fn apply_genre_atmo_hydro(w: &mut World, genre: &str) {
-    if genre != "opera" && genre != "firm" {
-        return;
-    }
-    let size = w.size as i64;
-    let atmo = w.atmosphere as i64;
-    let new_atmo = if size < 3 || (size < 4 && atmo < 3) {
-        0
-    } else if (size == 3 || size == 4) && (3..=5).contains(&atmo) {
-        1
-    } else if (size == 3 || size == 4) && atmo > 5 {
-        10
-    } else {
-        atmo
-    };
-    w.atmosphere = new_atmo as u8;
-    let mut hydro = w.hydrographics as i64;
-    let a = w.atmosphere as i64;
-    if a < 2 {
-        hydro -= 6;
-    }
-    if a == 2 || a == 3 || a == 11 || a == 12 {
-        hydro -= 4;
+impl NativeModule for T5Module {
+    /// Native Intelligent Life (T5 page 436), by the `sophonts` setting.
+    fn native_status(&self, w: &World) -> Result<String> {
+        let varied = runtime::sophonts() == "varied";
+        Ok(if varied && w.population >= 7 {
+            if w.atmosphere <= 1 { "Exotic".into() } else { "Native".into() }
+        } else if w.population >= 7 {
+            "Settled".into()
+        } else if (1..=6).contains(&w.population) {
+            "Colony".into()
+        } else {
+            String::new()
+        })
     }
-    w.hydrographics = hydro.max(0) as u8;
 }
 
-fn firm_pop_strip(w: &World, genre: &str, mut pop: i64, mut port_roll: i64) -> (i64, i64) {
-    if genre != "firm" {
-        return (pop, port_roll);
-    }
-    let size = w.size as i64;
-    if size < 3 || size > 9 {
-        pop -= 1;
-    }
-    let atmo_dm = [-1, -1, -1, -1, -1, 1, 1, -1, 1, -1, -1, -1, -1, -1, -1, -1];
-    let a = w.atmosphere as usize;
-    if a < atmo_dm.len() {
-        pop += atmo_dm[a];
+impl NativeModule for NoModule {
+    fn native_status(&self, _w: &World) -> Result<String> {
+        Ok(String::new())
     }
-    port_roll = (port_roll + 7 - pop.max(0)).max(0);
-    (pop, port_roll)
 }
 
-const GRAVITY: [f64; 16] = [
-    0.0, 0.05, 0.15, 0.25, 0.35, 0.45, 0.7, 0.9, 1.0, 1.25, 1.4, 1.6, 1.9, 2.2, 2.5, 2.8,
-];
-
-fn gravity_for(size: u8) -> f64 {
-    *GRAVITY.get(size as usize).unwrap_or(&0.0)
+/// Surface gravity in G, from the ruleset's `tables.gravity` (hundredths of a G by
+/// size); None when the ruleset has no such table.
+fn gravity_for(size: u8) -> Option<f64> {
+    let g = runtime::ruleset().table_entry("gravity", size as i64)?;
+    Some(g.as_int() as f64 / 100.0)
 }
 
 fn is_hot_star(t: StarType) -> bool {
@@ -254,7 +220,7 @@ fn cap_colony_population(w: &World, mut pop: i64, star_type: Option<StarType>) -
         }
     }
     let g = gravity_for(w.size);
-    if !(0.4..=1.5).contains(&g) && pop > 6 {
+    if g.is_some_and(|g| !(0.4..=1.5).contains(&g)) && pop > 6 {
         pop = 6;
     }
     pop
@@ -262,35 +228,134 @@ fn cap_colony_population(w: &World, mut pop: i64, star_type: Option<StarType>) -
 
 // ---- starport bases (ruleset-driven) -----------------------------------
 
-fn generate_bases(port: char) -> Result<Vec<Base>> {
+/// The kinds the generator rolled before bases came from the ruleset. They keep
+/// drawing on the generation stream, so a seed still makes the same sector; every
+/// other kind rolls on the hex's own stream and can be added or tuned freely.
+const CORE_BASES: [&str; 4] = ["naval", "scout", "depot", "way"];
+
+fn generate_bases(ctx: &Context, trade_codes: &[String]) -> Vec<Base> {
     let rs = runtime::ruleset();
-    let p = port.to_string();
-    let mut bases = Vec::new();
-    for (kind, base) in [
-        ("naval", Base::Naval),
-        ("scout", Base::Scout),
-        ("depot", Base::Depot),
-        ("way", Base::Way),
-    ] {
-        if let Some(th) = rs.base_threshold(kind, &p) {
-            if rs.base_meets(rng::roll_2d6()? as i64, th) {
-                bases.push(base);
-            }
+    let mut ctx = ctx.clone();
+    for code in trade_codes {
+        ctx.insert(code.clone(), Value::Int(1));
+    }
+    let check = |kind: &String| {
+        if CORE_BASES.contains(&kind.as_str()) {
+            rs.base_check(kind, &ctx)
+        } else {
+            rng::with_stream(&format!("bases/{kind}"), || rs.base_check(kind, &ctx))
         }
+    };
+    rs.base_kinds()
+        .iter()
+        .filter(|kind| check(kind))
+        .filter_map(|kind| Base::from_kind(kind, rs.base_code(kind)))
+        .collect()
+}
+
+/// Look the port class up in the ruleset's facilities table (TL-dependent rows).
+/// Its dice (berthing, highports) roll on the hex's own "facilities" stream.
+fn starport_facilities(port: char, ctx: &Context) -> Option<Facilities> {
+    let rs = runtime::ruleset();
+    let p = port.to_string();
+    if !rs.has_facilities(&p) {
+        return None;
     }
-    Ok(bases)
+    let flag = |key: &str| rs.facility_value(&p, key, ctx).unwrap_or(0) > 0;
+    Some(Facilities {
+        fuel: rs.facility_text(&p, "fuel", ctx),
+        shipyard: rs.facility_text(&p, "shipyard", ctx),
+        repair: rs.facility_text(&p, "repair", ctx),
+        berthing: rs.facility_value(&p, "berthing", ctx).unwrap_or(0).max(0),
+        highport: flag("highport"),
+        downport: flag("downport"),
+    })
 }
 
 // ---- extensions module (post-pass) -------------------------------------
 
-/// Run the ruleset's extensions module (if any), then native status. Called once the
+/// Run the ruleset's extensions module, then native status. Called once the
 /// system's gas-giant and belt counts are known. Mirrors the Go buildExtensions.
-pub fn build_extensions(w: &mut World, gas_giants: i64, belts: i64) {
-    let module = runtime::ruleset().module_for("extensions").unwrap_or_else(|_| "t5".into());
-    if module != "none" {
+pub fn build_extensions(w: &mut World, gas_giants: i64, belts: i64) -> Result<()> {
+    let err = AstromapperError::RulesetError;
+    runtime::extensions_module().map_err(err)?.build_extensions(w, gas_giants, belts)?;
+    w.native = runtime::native_module().map_err(err)?.native_status(w)?;
+    w.military = rng::with_stream("military", || build_military(w));
+    w.culture = rng::with_stream("culture", || build_culture(w));
+    Ok(())
+}
+
+/// Roll the ruleset's `culture:` tables from Cx, government, and population, on the
+/// hex's own "culture" stream (a new table row must not reshuffle later systems).
+fn build_culture(w: &World) -> Option<Culture> {
+    let rs = runtime::ruleset();
+    if !rs.has_culture() || w.population == 0 {
+        return None;
+    }
+    let mut ctx = w.context();
+    for (k, v) in ["homo", "acc", "strange", "sym"].iter().zip(w.cx) {
+        ctx.insert(k.to_string(), Value::Int(v));
+    }
+    Some(Culture {
+        offworlders: rs.culture_offworlders(&ctx),
+        religions: rs.culture_list("religions", &ctx),
+        languages: rs.culture_list("languages", &ctx),
+        quirks: rs.culture_list("quirks", &ctx),
+    })
+}
+
+/// Evaluate the ruleset's `military:` formulas over the finished world. Runs after
+/// the extensions module so RU and Ix are known (both 0 when it is `none`), on the
+/// hex's own "military" stream so its dice never shift the rest of the sector.
+fn build_military(w: &World) -> Option<Military> {
+    let rs = runtime::ruleset();
+    if !rs.has_military() || w.population == 0 {
+        return None;
+    }
+    let naval = w.bases.contains(&Base::Naval);
+    let mut ctx = w.context();
+    ctx.insert("ix".into(), Value::Int(w.ix));
+    ctx.insert("ru".into(), Value::Int(w.ru));
+    ctx.insert("naval".into(), Value::Int(naval as i64));
+
+    let step = |name: &str, ctx: &mut Context| -> i64 {
+        let v = rs.military_step(name, ctx).unwrap_or(0).max(0);
+        ctx.insert(name.into(), Value::Int(v));
+        v
+    };
+    let defence = step("defence", &mut ctx);
+    let budget = step("budget", &mut ctx);
+    let share = step("navy_share", &mut ctx).min(100);
+    let navy_budget = budget * share / 100;
+    ctx.insert("navy".into(), Value::Int(navy_budget));
+    ctx.insert("army".into(), Value::Int(budget - navy_budget));
+    let fleet_tonnage = step("fleet", &mut ctx);
+    let max_ship_tl = step("max_ship_tl", &mut ctx);
+    let squadrons = if naval { step("squadrons", &mut ctx).max(1) } else { 0 };
+
+    Some(Military {
+        defence,
+        budget,
+        navy_budget,
+        army_budget: budget - navy_budget,
+        fleet_tonnage,
+        max_ship_tl,
+        squadrons,
+    })
+}
+
+impl ExtensionsModule for NoModule {
+    fn build_extensions(&self, _w: &mut World, _gas_giants: i64, _belts: i64) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ExtensionsModule for T5Module {
+    /// Ix / Ex / Cx and Resource Units (T5 page 435).
+    fn build_extensions(&self, w: &mut World, gas_giants: i64, belts: i64) -> Result<()> {
         build_extensions_t5(w, gas_giants, belts);
+        Ok(())
     }
-    w.native = native_status(w);
 }
 
 fn build_extensions_t5(w: &mut World, gas_giants: i64, belts: i64) {
@@ -2,6 +2,7 @@
 //! the CLI is: explicit flag > config file > defaults. Mirrors the Ruby/Go config.
 
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -32,6 +33,18 @@ fn d_jump() -> i64 {
 fn d_min() -> usize {
     2
 }
+fn d_route_mode() -> String {
+    "legacy".into()
+}
+fn d_route_jump() -> i64 {
+    4
+}
+fn d_main_min() -> usize {
+    3
+}
+fn d_thrust() -> f64 {
+    1.0
+}
 fn d_opacity() -> f64 {
     0.85
 }
@@ -62,6 +75,28 @@ pub struct Config {
     pub island_min: usize,
     #[serde(default = "d_opacity")]
     pub island_opacity: f64,
+    #[serde(default)]
+    pub trade_routes: bool, // WTN/BTN trade routes (SVG layer, JSON, `.tab`)
+    #[serde(default)]
+    pub trade_jump: i64, // 0 = the ruleset's trade.max_jump
+    #[serde(default = "d_route_mode")]
+    pub route_mode: String, // legacy | spanning | trade
+    #[serde(default = "d_route_jump")]
+    pub route_jump: i64,
+    #[serde(default)]
+    pub route_styles: BTreeMap<String, String>, // route class -> CSS declarations
+    #[serde(default)]
+    pub mains: bool, // outline mains on the SVG (they are always listed)
+    #[serde(default = "d_main_min")]
+    pub main_min: usize,
+    #[serde(default)]
+    pub xboat: bool, // build the X-boat network (SVG layer, JSON, TravellerMap routes)
+    #[serde(default)]
+    pub jump_graph: i64, // 0 = off; else build the jump graph up to this jump
+    #[serde(default = "d_thrust")]
+    pub thrust: f64, // G for the jump-point travel times in the .txt
+    #[serde(default)]
+    pub genres: serde_yaml::Mapping, // extra/overriding genres, merged over the ruleset's
 }
 
 impl Default for Config {
@@ -94,8 +129,8 @@ type: sector            # sector | volume
 name: \"{name}\"
 density: scattered      # extra-galactic | rift | sparse | dunbar | scattered | dense | cluster | core
 seed:                   # blank = random (a Crawford code is printed); or a code/string
-genre: normal           # firm (realistic, M-dwarf-heavy) | normal | opera (Sun-like)
-ruleset: t5             # t5 | cepheus | a custom rules/<name>.yml in this directory
+genre: normal           # firm (realistic, M-dwarf-heavy) | normal | opera (Sun-like) | one under `genres:`
+ruleset: t5             # t5 | cepheus | mgt2 | classic | a custom rules/<name>.yml here
 sophonts: human         # human (Settled/Colony) | varied (alien sophonts)
 prune_isolated: true    # drop systems with no neighbour within jump-4 (lone dots)
 
@@ -104,6 +139,40 @@ islands: true
 island_jump: 2          # systems within this many jumps form one island
 island_min: 2           # minimum systems per island
 island_opacity: 0.85    # 0.0 (invisible) .. 1.0 (solid)
+
+# Trade routes (World/Bilateral Trade Numbers), drawn as their own SVG layer
+trade_routes: false
+trade_jump: 0           # farthest trading pair, in jumps; 0 = the ruleset's max_jump
+
+# Jump-route lines on the SVG: legacy (one line per slope, as the Ruby version drew
+# them), spanning (nearest-neighbour spanning tree) or trade (BTN-weighted tree,
+# lines classed major/intermediate/minor)
+route_mode: legacy
+route_jump: 4           # longest jump drawn by spanning/trade
+route_styles: {{}}        # CSS per line class, e.g. {{ major: \"stroke: #c0392b; stroke-width: 8\" }}
+
+# T5 mains (chains of systems linked at jump-1), listed in the .txt and JSON
+mains: false            # also outline them on the SVG
+main_min: 3             # minimum systems per main
+
+# X-boat courier network between the ruleset's hubs; relay worlds become way
+# stations (W). Drawn on the SVG, listed in the JSON, and written as TravellerMap
+# route metadata (.xml)
+xboat: false
+
+# Jump graph (systems + edges up to jump-N, with BTN), added to the JSON and written
+# as .dot (Graphviz) and .graphml; 0 = off
+jump_graph: 0
+
+# Jump shadows: each system in the .txt lists its stars' and mainworld's 100D limits
+# and the time from the nearest jump point to the mainworld at this thrust
+thrust: 1               # G
+
+# Extra genres, merged over the ruleset's `genres:` (same fields: census, atmo,
+# hydro, pop_dm, port_dm), e.g.
+# genres:
+#   hard-sf: {{ census: natural, pop_dm: \"-2\" }}
+genres: {{}}
 "
     )
 }
@@ -119,6 +188,13 @@ mod tests {
         assert_eq!(d.density, "scattered");
         assert!(d.islands);
         assert_eq!(d.island_jump, 2);
+        assert!(!d.trade_routes, "trade routes are opt-in");
+        assert!(!d.mains);
+        assert_eq!(d.route_mode, "legacy");
+        assert!(d.route_styles.is_empty());
+        assert_eq!(d.main_min, 3);
+        assert_eq!(d.thrust, 1.0);
+        assert!(d.genres.is_empty());
     }
 
     #[test]
@@ -140,5 +216,6 @@ mod tests {
         let cfg: Config = serde_yaml::from_str(&template("Spinward Marches")).unwrap();
         assert_eq!(cfg.name, "Spinward Marches");
         assert_eq!(cfg.ruleset, "t5");
+        assert!(!cfg.trade_routes);
     }
 }
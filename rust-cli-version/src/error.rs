@@ -17,6 +17,9 @@ pub enum AstromapperError {
     #[error("Format error: {0}")]
     FormatError(String),
     
+    #[error("Ruleset error: {0}")]
+    RulesetError(String),
+
     #[error("JSON serialization error: {0}")]
     JsonError(#[from] serde_json::Error),
 }
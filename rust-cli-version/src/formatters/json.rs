@@ -12,4 +12,9 @@ impl JsonFormatter {
     pub fn format_volume(volume: &Volume) -> Result<String> {
         Ok(serde_json::to_string_pretty(volume)?)
     }
+
+    /// Read back a sector written by `format_sector`.
+    pub fn parse_sector(json: &str) -> Result<Sector> {
+        Ok(serde_json::from_str(json)?)
+    }
 }
\ No newline at end of file
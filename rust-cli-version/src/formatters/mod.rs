@@ -4,5 +4,5 @@ pub mod svg;
 pub mod json;
 
 pub use ascii::AsciiFormatter;
-pub use svg::SvgGenerator;
+pub use svg::{RouteMode, SvgGenerator};
 pub use json::JsonFormatter;
\ No newline at end of file
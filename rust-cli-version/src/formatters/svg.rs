@@ -1,6 +1,28 @@
+use crate::models::world::Base;
 use crate::models::{Sector, Volume};
+use crate::rules::runtime;
 use std::fmt::Write;
 
+/// How the jump-route layer is chosen: the Ruby slope-bucket heuristic, or a
+/// spanning forest of the jump graph (nearest-neighbour or trade-weighted).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteMode {
+    Legacy,
+    Spanning,
+    Trade,
+}
+
+impl RouteMode {
+    pub fn from_name(name: &str) -> Option<RouteMode> {
+        match name {
+            "legacy" => Some(RouteMode::Legacy),
+            "spanning" => Some(RouteMode::Spanning),
+            "trade" => Some(RouteMode::Trade),
+            _ => None,
+        }
+    }
+}
+
 pub struct SvgGenerator {
     rows: usize,
     columns: usize,
@@ -13,6 +35,11 @@ pub struct SvgGenerator {
     island_jump: i64,
     island_min: usize,
     island_opacity: f64,
+    show_trade: bool,
+    show_mains: bool,
+    route_mode: RouteMode,
+    route_jump: i64,
+    route_styles: Vec<(String, String)>, // (route class, CSS declarations)
 }
 
 impl SvgGenerator {
@@ -34,6 +61,11 @@ impl SvgGenerator {
             island_jump: 2,
             island_min: 2,
             island_opacity: 0.85,
+            show_trade: true,
+            show_mains: false,
+            route_mode: RouteMode::Legacy,
+            route_jump: 4,
+            route_styles: Vec::new(),
         }
     }
 
@@ -45,6 +77,32 @@ impl SvgGenerator {
         self
     }
 
+    /// Draw the sector's classed trade routes (if it has any) as their own layer.
+    pub fn with_trade_routes(mut self, show: bool) -> Self {
+        self.show_trade = show;
+        self
+    }
+
+    /// Outline the sector's T5 mains (jump-1 chains) as their own layer.
+    pub fn with_mains(mut self, show: bool) -> Self {
+        self.show_mains = show;
+        self
+    }
+
+    /// Choose the jump-route layer and, for the graph modes, the longest jump drawn.
+    pub fn with_routes(mut self, mode: RouteMode, max_jump: i64) -> Self {
+        self.route_mode = mode;
+        self.route_jump = max_jump;
+        self
+    }
+
+    /// Extra CSS per route class (`line1`..`line4`, or a trade class such as `major`),
+    /// applied after the built-in styles.
+    pub fn with_route_styles(mut self, styles: Vec<(String, String)>) -> Self {
+        self.route_styles = styles;
+        self
+    }
+
     /// Convenience: generate with default island settings.
     pub fn generate_sector(sector: &Sector) -> String {
         SvgGenerator::new(sector.name.clone()).generate(sector)
@@ -58,7 +116,10 @@ impl SvgGenerator {
         svg.push_str(&gen.tract_marks());
         svg.push_str(&gen.hex_grid());
         svg.push_str(&gen.islands(sector));
+        svg.push_str(&gen.mains(sector));
         svg.push_str(&gen.routes(sector));
+        svg.push_str(&gen.trade_routes(sector));
+        svg.push_str(&gen.xboat_routes(sector));
 
         // Draw worlds
         for row in 0..sector.height {
@@ -119,9 +180,74 @@ impl SvgGenerator {
         out
     }
 
-    /// Jump routes between systems — a port of Ruby build_routes / calc_route: per
-    /// source, keep one line per slope bucket and never the reverse of an existing route.
+    /// Outline each main with the island geometry at jump-1 (a main is exactly one
+    /// jump-1 cluster, so the borders match the detected chains).
+    fn mains(&self, sector: &Sector) -> String {
+        if !self.show_mains || sector.mains.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("<g class='mains'>\n");
+        for m in &sector.mains {
+            let hexes: Vec<(i64, i64)> = m
+                .hexes
+                .iter()
+                .filter_map(|h| crate::models::sector::parse_hex(h))
+                .collect();
+            let groups = crate::formatters::islands::borders(
+                &hexes, self.side, self.factor, self.columns as i64, self.rows as i64, 1, 1,
+            );
+            let _ = write!(out, "  <g><title>{} ({})</title>", m.name, m.hexes.len());
+            for ring in groups.iter().flat_map(|g| g.loops.iter()) {
+                let pts: Vec<String> = ring.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+                let _ = write!(out, "<polygon points='{}'/>", pts.join(" "));
+            }
+            out.push_str("</g>\n");
+        }
+        out.push_str("</g><!--/mains-->\n");
+        out
+    }
+
+    /// Jump routes between systems, drawn per the route mode.
     fn routes(&self, sector: &Sector) -> String {
+        match self.route_mode {
+            RouteMode::Legacy => self.legacy_routes(sector),
+            RouteMode::Spanning => self.graph_routes(sector, false),
+            RouteMode::Trade => self.graph_routes(sector, true),
+        }
+    }
+
+    /// A spanning forest of the jump-`route_jump` graph: each line is classed by its
+    /// distance (`lineN`) and, when trade-weighted, by the route class its BTN earns.
+    fn graph_routes(&self, sector: &Sector, by_trade: bool) -> String {
+        let graph = crate::navigation::JumpGraph::build(sector, self.route_jump);
+        let rs = runtime::ruleset();
+        let mut out = String::from("<g class='routes'>\n");
+        for e in graph.spanning_edges(by_trade) {
+            let (Some((c1, r1)), Some((c2, r2))) = (
+                crate::models::sector::parse_hex(&e.from),
+                crate::models::sector::parse_hex(&e.to),
+            ) else {
+                continue;
+            };
+            let (x1, y1) = self.center_of(c1 as usize, r1 as usize);
+            let (x2, y2) = self.center_of(c2 as usize, r2 as usize);
+            let class = match by_trade.then(|| rs.trade_route_class(e.btn)).flatten() {
+                Some(c) => format!("line{} {}", e.jump, c),
+                None => format!("line{}", e.jump),
+            };
+            let _ = writeln!(
+                out,
+                "<!-- {}:{} --><line class='{}' x1='{}' y1='{}' x2='{}' y2='{}' />",
+                e.from, e.to, class, x1 as i64, y1 as i64, x2 as i64, y2 as i64
+            );
+        }
+        out.push_str("</g>\n");
+        out
+    }
+
+    /// Legacy jump routes — a port of Ruby build_routes / calc_route: per source, keep
+    /// one line per slope bucket and never the reverse of an existing route.
+    fn legacy_routes(&self, sector: &Sector) -> String {
         let mut present: std::collections::HashSet<(i64, i64)> = std::collections::HashSet::new();
         let mut keys: Vec<(i64, i64)> = Vec::new();
         for row in 0..sector.height {
@@ -157,6 +283,57 @@ impl SvgGenerator {
         out
     }
 
+    /// The trade-route layer: one line per classed route, styled by its class.
+    fn trade_routes(&self, sector: &Sector) -> String {
+        if !self.show_trade || sector.trade_routes.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("<g class='trade'>\n");
+        for r in &sector.trade_routes {
+            let (Some((c1, r1)), Some((c2, r2))) = (
+                crate::models::sector::parse_hex(&r.from),
+                crate::models::sector::parse_hex(&r.to),
+            ) else {
+                continue;
+            };
+            let (x1, y1) = self.center_of(c1 as usize, r1 as usize);
+            let (x2, y2) = self.center_of(c2 as usize, r2 as usize);
+            let _ = writeln!(
+                out,
+                "  <line class='{}' x1='{}' y1='{}' x2='{}' y2='{}'><title>{}-{} {} BTN {}</title></line>",
+                r.class, x1 as i64, y1 as i64, x2 as i64, y2 as i64, r.from, r.to, r.class, r.btn
+            );
+        }
+        out.push_str("</g><!--/trade-->\n");
+        out
+    }
+
+    /// The X-boat layer, drawn whenever the sector carries a network.
+    fn xboat_routes(&self, sector: &Sector) -> String {
+        let Some(net) = &sector.xboat else { return String::new() };
+        if net.links.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("<g class='xboat'>\n");
+        for l in &net.links {
+            let (Some((c1, r1)), Some((c2, r2))) = (
+                crate::models::sector::parse_hex(&l.from),
+                crate::models::sector::parse_hex(&l.to),
+            ) else {
+                continue;
+            };
+            let (x1, y1) = self.center_of(c1 as usize, r1 as usize);
+            let (x2, y2) = self.center_of(c2 as usize, r2 as usize);
+            let _ = writeln!(
+                out,
+                "  <line x1='{}' y1='{}' x2='{}' y2='{}'><title>X-boat {}-{} J{}</title></line>",
+                x1 as i64, y1 as i64, x2 as i64, y2 as i64, l.from, l.to, l.jump
+            );
+        }
+        out.push_str("</g><!--/xboat-->\n");
+        out
+    }
+
     fn calc_route(
         &self,
         src: (i64, i64),
@@ -168,7 +345,7 @@ impl SvgGenerator {
         if d == 0 {
             return None;
         }
-        if routes.get(&src).map_or(false, |v| v.contains(&tgt)) {
+        if routes.get(&src).is_some_and(|v| v.contains(&tgt)) {
             return None; // already the reverse of an existing route
         }
         routes.entry(tgt).or_default().push(src);
@@ -178,7 +355,7 @@ impl SvgGenerator {
         let src_pix = (sx as i64, sy as i64);
         let dst_pix = (dx as i64, dy as i64);
         let m = slope_pix(src_pix, dst_pix);
-        if slopes.get(&src).map_or(false, |v| v.contains(&m)) {
+        if slopes.get(&src).is_some_and(|v| v.contains(&m)) {
             return None; // already a route on this slope
         }
         slopes.entry(src).or_default().push(m);
@@ -201,47 +378,71 @@ impl SvgGenerator {
         let (cx, cy) = self.center_of(col, row);
         
         if let Some(world) = &volume.world {
-            write!(&mut output, "<!-- Volume: {:02}{:02} -->\n", col, row).unwrap();
+            writeln!(&mut output, "<!-- Volume: {:02}{:02} -->", col, row).unwrap();
             
             // Draw planet or belt
             if world.size == 0 {
                 output.push_str(&self.draw_belt(cx, cy));
             } else {
-                write!(&mut output, 
-                    "    <circle class='planet' cx='{}' cy='{}' r='{}' />\n",
+                writeln!(&mut output, 
+                    "    <circle class='planet' cx='{}' cy='{}' r='{}' />",
                     cx as i32, cy as i32, (self.side / 7.0) as i32
                 ).unwrap();
             }
             
-            // Starport
-            write!(&mut output,
-                "    <text class='spaceport' x='{}' y='{}'>{}</text>\n",
-                cx as i32, (cy + self.side / 2.0) as i32, world.starport
+            // Starport, with its facilities as a hover tooltip
+            let tooltip = world
+                .facilities
+                .as_ref()
+                .map(|f| format!("<title>Starport {}: {}</title>", world.starport, f.describe()))
+                .unwrap_or_default();
+            writeln!(&mut output,
+                "    <text class='spaceport' x='{}' y='{}'>{}{}</text>",
+                cx as i32, (cy + self.side / 2.0) as i32, world.starport, tooltip
             ).unwrap();
             
             // UWP
-            write!(&mut output,
-                "    <text x='{}' y='{}'>{}</text>\n",
+            writeln!(&mut output,
+                "    <text x='{}' y='{}'>{}</text>",
                 cx as i32, (cy + self.side / 1.3) as i32, world.uwp
             ).unwrap();
             
             // Name
-            write!(&mut output,
-                "    <text x='{}' y='{}'>{}</text>\n",
+            writeln!(&mut output,
+                "    <text x='{}' y='{}'>{}</text>",
                 cx as i32, (cy - self.side / 2.1) as i32, world.name
             ).unwrap();
             
-            // Bases
-            if world.bases_string().contains('N') {
-                write!(&mut output,
-                    "    <text class='symbol N' x='{}' y='{}'>⚓</text>\n",
-                    (cx - self.side / 1.8) as i32, (cy - self.side / 6.0) as i32
-                ).unwrap();
+            // Bases: naval top-left, scout bottom-left, every other kind top-right
+            let rs = runtime::ruleset();
+            let glyph = |b: &Base| {
+                b.to_code()
+                    .chars()
+                    .next()
+                    .and_then(|c| rs.base_glyph(c))
+                    .unwrap_or_else(|| b.glyph())
+            };
+            for (base, dx, dy) in [(Base::Naval, -1.8, -6.0), (Base::Scout, -1.8, 2.4)] {
+                if world.bases.contains(&base) {
+                    writeln!(&mut output,
+                        "    <text class='symbol {}' x='{}' y='{}'>{}</text>",
+                        base.to_code(), (cx + self.side / dx) as i32,
+                        (cy + self.side / dy) as i32, glyph(&base)
+                    ).unwrap();
+                }
             }
-            if world.bases_string().contains('S') {
-                write!(&mut output,
-                    "    <text class='symbol S' x='{}' y='{}'>⚜</text>\n",
-                    (cx - self.side / 1.8) as i32, (cy + self.side / 2.4) as i32
+            let others: Vec<&Base> = world
+                .bases
+                .iter()
+                .filter(|b| !matches!(b, Base::Naval | Base::Scout))
+                .collect();
+            if !others.is_empty() {
+                let codes: Vec<_> = others.iter().map(|b| b.to_code()).collect();
+                let glyphs: Vec<String> = others.iter().map(|b| glyph(b)).collect();
+                writeln!(&mut output,
+                    "    <text class='symbol bases' x='{}' y='{}'><title>Bases: {}</title>{}</text>",
+                    (cx + self.side / 1.8) as i32, (cy - self.side / 6.0) as i32,
+                    codes.join(""), glyphs.join("")
                 ).unwrap();
             }
             
@@ -249,8 +450,8 @@ impl SvgGenerator {
             if world.gas_giant {
                 let gx = cx + self.side / 1.8;
                 let gy = cy + self.side / 3.0;
-                write!(&mut output,
-                    "    <g class='gas-giant'>\n      <ellipse cx='{}' cy='{}' rx='{}' ry='{}' />\n      <circle cx='{}' cy='{}' r='{}' />\n    </g>\n",
+                writeln!(&mut output,
+                    "    <g class='gas-giant'>\n      <ellipse cx='{}' cy='{}' rx='{}' ry='{}' />\n      <circle cx='{}' cy='{}' r='{}' />\n    </g>",
                     gx as i32, gy as i32, 
                     (self.side / 6.5) as i32, (self.side / 13.0 * 0.3) as i32,
                     gx as i32, gy as i32, (self.side / 15.6) as i32
@@ -268,8 +469,8 @@ impl SvgGenerator {
         for i in 0..7 {
             let x = cx + ((i * 17 % (self.side / 3.0) as i32) as f64 - self.side / 6.0);
             let y = cy + ((i * 23 % (self.side / 3.0) as i32) as f64 - self.side / 6.0);
-            write!(&mut output,
-                "      <circle cx='{}' cy='{}' r='{}' />\n",
+            writeln!(&mut output,
+                "      <circle cx='{}' cy='{}' r='{}' />",
                 x as i32, y as i32, (self.side / 15.0) as i32
             ).unwrap();
         }
@@ -295,13 +496,13 @@ impl SvgGenerator {
                 let x = col as f64 * subsector_width;
                 
                 if idx < letters.len() {
-                    write!(&mut output,
-                        "<rect x='{}' y='{}' width='{}' height='{}' />\n",
+                    writeln!(&mut output,
+                        "<rect x='{}' y='{}' width='{}' height='{}' />",
                         x as i32, y as i32, 
                         subsector_width as i32, subsector_height as i32
                     ).unwrap();
-                    write!(&mut output,
-                        "<text x='{}' y='{}'>{}</text>\n",
+                    writeln!(&mut output,
+                        "<text x='{}' y='{}'>{}</text>",
                         (x + 70.0) as i32, (y + 110.0) as i32, letters[idx]
                     ).unwrap();
                 }
@@ -310,8 +511,8 @@ impl SvgGenerator {
         }
         
         output.push_str("</g>\n");
-        write!(&mut output,
-            "<text class='namestamp' x='30' y='{}'>{}</text>\n",
+        writeln!(&mut output,
+            "<text class='namestamp' x='30' y='{}'>{}</text>",
             (self.height - 40.0) as i32, self.name
         ).unwrap();
         
@@ -329,8 +530,8 @@ impl SvgGenerator {
                 if col % 2 == 0 {
                     y += self.side * self.factor / 2.0;
                 }
-                write!(&mut output,
-                    "    <text x='{}' y='{}'>{:02}{:02}</text>\n",
+                writeln!(&mut output,
+                    "    <text x='{}' y='{}'>{:02}{:02}</text>",
                     x as i32, y as i32, col, row
                 ).unwrap();
             }
@@ -419,6 +620,10 @@ impl SvgGenerator {
   text.symbol.N {{
     font-size: 9px;
   }}
+  text.symbol.bases {{
+    font-size: 8px;
+    text-anchor: start;
+  }}
   g.volumes text {{
     opacity: 0.5;
   }}
@@ -456,6 +661,14 @@ impl SvgGenerator {
     fill: none;
     stroke-linejoin: round;
   }}
+  g.mains polygon {{
+    stroke-width: 3;
+    fill: none;
+    stroke: #2c3e50;
+    stroke-dasharray: 10,4;
+    stroke-linejoin: round;
+    opacity: 0.7;
+  }}
   line {{
     opacity: 0.3;
     stroke-linecap: round;
@@ -465,6 +678,14 @@ impl SvgGenerator {
   line.line2 {{ stroke-width: 3; }}
   line.line3 {{ stroke-width: 2; stroke-dasharray: 5,5,1,5; opacity: 0.6; }}
   line.line4 {{ stroke-width: 1.5; stroke-dasharray: 2,6; }}
+  g.trade line {{ opacity: 0.55; stroke: #d35400; }}
+  g.trade line.major {{ stroke-width: 7; }}
+  g.trade line.intermediate {{ stroke-width: 4; stroke: #e67e22; }}
+  g.trade line.minor {{ stroke-width: 2; stroke: #f0b27a; }}
+  g.routes line.major {{ stroke-width: 6; stroke: #d35400; opacity: 0.6; stroke-dasharray: none; }}
+  g.routes line.intermediate {{ stroke-width: 4; stroke: #e67e22; opacity: 0.5; stroke-dasharray: none; }}
+  g.routes line.minor {{ stroke-width: 2.5; stroke: #f0b27a; opacity: 0.5; stroke-dasharray: none; }}
+  g.xboat line {{ opacity: 0.8; stroke: #8e44ad; stroke-width: 3; stroke-dasharray: 12,4; }}
 
   /* Light mode (default) */
   svg {{
@@ -561,8 +782,16 @@ impl SvgGenerator {
       stroke: #ABB2BF;
     }}
   }}
-  </style>
-"#, self.width as i32, self.height as i32, self.name)
+{}  </style>
+"#, self.width as i32, self.height as i32, self.name, self.route_style_css())
+    }
+
+    fn route_style_css(&self) -> String {
+        let mut css = String::new();
+        for (class, decl) in &self.route_styles {
+            let _ = writeln!(css, "  g.routes line.{} {{ {} }}", class, decl.trim());
+        }
+        css
     }
 }
 
@@ -610,4 +839,103 @@ fn slope_pix(a: (i64, i64), b: (i64, i64)) -> f64 {
         answer = 0.1;
     }
     (answer * 10.0).round() / 10.0
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::World;
+    use crate::rules::Ruleset;
+
+    /// Three worlds in a row (0101, 0201 at jump-1, 0401 two further), and one far off.
+    fn sector() -> Sector {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("t".into(), 32, 40);
+        for (c, r, wtn) in [(1, 1, 7), (2, 1, 7), (4, 1, 3), (9, 9, 5)] {
+            let mut w = World::new(0, 0);
+            w.population = 6;
+            w.wtn = wtn;
+            let mut v = Volume::new(r - 1, c - 1);
+            v.world = Some(w);
+            s.set_volume(r - 1, c - 1, v);
+        }
+        s
+    }
+
+    /// The class attribute of each route line, keyed by its "from:to" comment.
+    fn lines(layer: &str) -> Vec<(String, String)> {
+        layer
+            .lines()
+            .filter_map(|l| {
+                let pair = l.strip_prefix("<!-- ")?.split(' ').next()?;
+                let class = l.split("class='").nth(1)?.split('\'').next()?;
+                Some((pair.to_string(), class.to_string()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn route_mode_names() {
+        assert_eq!(RouteMode::from_name("legacy"), Some(RouteMode::Legacy));
+        assert_eq!(RouteMode::from_name("spanning"), Some(RouteMode::Spanning));
+        assert_eq!(RouteMode::from_name("trade"), Some(RouteMode::Trade));
+        assert_eq!(RouteMode::from_name("Trade"), None);
+        assert_eq!(RouteMode::from_name("mst"), None);
+    }
+
+    #[test]
+    fn graph_route_classes() {
+        let s = sector();
+        let spanning = SvgGenerator::new("t".into()).with_routes(RouteMode::Spanning, 4);
+        assert_eq!(
+            lines(&spanning.routes(&s)),
+            [("0101:0201".into(), "line1".into()), ("0201:0401".into(), "line2".into())]
+        );
+
+        // Trade mode adds the class each BTN earns: 7+7 is major, 7+3 minor.
+        let trade = SvgGenerator::new("t".into()).with_routes(RouteMode::Trade, 4);
+        assert_eq!(
+            lines(&trade.routes(&s)),
+            [("0101:0201".into(), "line1 major".into()), ("0201:0401".into(), "line2 minor".into())]
+        );
+    }
+
+    #[test]
+    fn route_jump_limits_the_graph() {
+        let s = sector();
+        let short = SvgGenerator::new("t".into()).with_routes(RouteMode::Spanning, 1);
+        assert_eq!(lines(&short.routes(&s)), [("0101:0201".into(), "line1".into())]);
+        let none = SvgGenerator::new("t".into()).with_routes(RouteMode::Trade, 0);
+        assert!(lines(&none.routes(&s)).is_empty());
+    }
+
+    #[test]
+    fn trade_layer_skips_bad_hexes() {
+        let mut s = sector();
+        let route = |from: &str, to: &str| crate::trade::TradeRoute {
+            from: from.into(),
+            to: to.into(),
+            jump: 1,
+            btn: 12,
+            class: "major".into(),
+        };
+        s.trade_routes = vec![route("0101", "0201"), route("7", "0201"), route("0101", "0001")];
+        let layer = SvgGenerator::new("t".into()).with_trade_routes(true).trade_routes(&s);
+        assert_eq!(layer.matches("<line").count(), 1, "{layer}");
+        assert!(layer.contains("<title>0101-0201 major BTN 12</title>"));
+    }
+
+    #[test]
+    fn route_styles_become_css() {
+        let styled = SvgGenerator::new("t".into()).with_route_styles(vec![
+            ("major".into(), " stroke: #c0392b; stroke-width: 8 ".into()),
+            ("line1".into(), "stroke-dasharray: 4".into()),
+        ]);
+        assert_eq!(
+            styled.route_style_css(),
+            "  g.routes line.major { stroke: #c0392b; stroke-width: 8 }\n  g.routes line.line1 { stroke-dasharray: 4 }\n"
+        );
+        assert!(styled.header().contains("g.routes line.major { stroke: #c0392b; stroke-width: 8 }"));
+        assert_eq!(SvgGenerator::new("t".into()).route_style_css(), "");
+    }
+}
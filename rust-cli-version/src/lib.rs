@@ -4,6 +4,8 @@ pub mod config;
 pub mod models;
 pub mod builders;
 pub mod formatters;
+pub mod trade;
+pub mod navigation;
 pub mod data;
 pub mod error;
 
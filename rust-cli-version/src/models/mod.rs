@@ -9,5 +9,5 @@ pub use world::World;
 pub use star::{Star, StarType, StarSize};
 pub use orbit::{Orbit, OrbitType, OrbitContent};
 pub use sector::Sector;
-pub use volume::Volume;
+pub use volume::{JumpShadow, Volume};
 pub use trade_codes::TradeCode;
\ No newline at end of file
@@ -19,6 +19,7 @@ pub trait Orbit {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum OrbitContent {
     Empty(EmptyOrbit),
     World(WorldOrbit),
@@ -1,7 +1,20 @@
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::ser::SerializeStruct;
 use std::collections::BTreeMap;
-use crate::models::Volume;
+use crate::models::{Volume, World};
+use crate::navigation::{JumpGraph, Main, XboatNetwork};
+use crate::rules::runtime;
+use crate::trade::TradeRoute;
+
+/// 1-based (col, row) of a "CCRR" hex label; None unless four digits, both non-zero.
+pub fn parse_hex(hex: &str) -> Option<(i64, i64)> {
+    if hex.len() != 4 || !hex.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let col: i64 = hex[..2].parse().ok()?;
+    let row: i64 = hex[2..].parse().ok()?;
+    (col > 0 && row > 0).then_some((col, row))
+}
 
 /// Traveller hex jump distance between 1-based hex coordinates, with even columns
 /// carrying the +1 offset so the metric matches the map geometry. Shared by isolation
@@ -14,14 +27,18 @@ pub fn hex_jump(c1: i64, r1: i64, c2: i64, r2: i64) -> i64 {
     (dx as f64 + (((dy - dx) as f64) / 2.0).max(0.0)).round() as i64
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct Sector {
     pub name: String,
     pub width: usize,
     pub height: usize,
     pub volumes: Vec<Vec<Option<Volume>>>,
-    #[serde(skip)]
-    pub ruleset_title: String, // names the active ruleset in the legends
+    pub ruleset_title: String, // names the active ruleset in the legends (not serialised)
+    pub thrust: f64,           // G for the jump-point travel times in the .txt (not serialised)
+    pub trade_routes: Vec<TradeRoute>,
+    pub jump_graph: Option<JumpGraph>, // serialised when built (--jump-graph)
+    pub mains: Vec<Main>,
+    pub xboat: Option<XboatNetwork>, // serialised when built (--xboat)
 }
 
 impl Serialize for Sector {
@@ -45,15 +62,65 @@ impl Serialize for Sector {
         }
         
         // Serialize as a struct with volumes as a map
-        let mut state = serializer.serialize_struct("Sector", 4)?;
+        let mut state = serializer.serialize_struct("Sector", 8)?;
         state.serialize_field("name", &self.name)?;
         state.serialize_field("volumes", &volumes_map)?;
         state.serialize_field("width", &self.width)?;
         state.serialize_field("height", &self.height)?;
+        state.serialize_field("trade_routes", &self.trade_routes)?;
+        state.serialize_field("mains", &self.mains)?;
+        match &self.jump_graph {
+            Some(g) => state.serialize_field("jump_graph", g)?,
+            None => state.skip_field("jump_graph")?,
+        }
+        match &self.xboat {
+            Some(x) => state.serialize_field("xboat", x)?,
+            None => state.skip_field("xboat")?,
+        }
         state.end()
     }
 }
 
+/// The on-disk JSON shape: volumes keyed by "CCRR" hex, as `serialize` writes them.
+#[derive(Deserialize)]
+struct SectorJson {
+    name: String,
+    width: usize,
+    height: usize,
+    volumes: BTreeMap<String, Volume>,
+    #[serde(default)]
+    trade_routes: Vec<TradeRoute>,
+    #[serde(default)]
+    jump_graph: Option<JumpGraph>,
+    #[serde(default)]
+    mains: Vec<Main>,
+    #[serde(default)]
+    xboat: Option<XboatNetwork>,
+}
+
+impl<'de> Deserialize<'de> for Sector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = SectorJson::deserialize(deserializer)?;
+        let mut sector = Sector::new(raw.name, raw.width, raw.height);
+        for (hex, volume) in raw.volumes {
+            let col: usize = hex.get(..2).and_then(|c| c.parse().ok()).unwrap_or(0);
+            let row: usize = hex.get(2..).and_then(|r| r.parse().ok()).unwrap_or(0);
+            if col == 0 || row == 0 {
+                return Err(serde::de::Error::custom(format!("bad hex key {hex:?}")));
+            }
+            sector.set_volume(row - 1, col - 1, volume);
+        }
+        sector.trade_routes = raw.trade_routes;
+        sector.jump_graph = raw.jump_graph;
+        sector.mains = raw.mains;
+        sector.xboat = raw.xboat;
+        Ok(sector)
+    }
+}
+
 impl Sector {
     pub fn new(name: String, width: usize, height: usize) -> Self {
         let mut volumes = Vec::with_capacity(height);
@@ -67,6 +134,11 @@ impl Sector {
             height,
             volumes,
             ruleset_title: String::new(),
+            thrust: 1.0,
+            trade_routes: Vec::new(),
+            jump_graph: None,
+            mains: Vec::new(),
+            xboat: None,
         }
     }
 
@@ -98,6 +170,15 @@ impl Sector {
                 }
             }
         }
+        if !self.trade_routes.is_empty() {
+            out.push_str("#\n# Trade routes (from, to, class, BTN, jump):\n");
+            for r in &self.trade_routes {
+                out.push_str(&format!(
+                    "# Route\t{}\t{}\t{}\t{}\t{}\n",
+                    r.from, r.to, r.class, r.btn, r.jump
+                ));
+            }
+        }
         out
     }
     
@@ -140,6 +221,33 @@ impl Sector {
         }
     }
 
+    /// Every mainworld with its 1-based (col, row) hex, in row-major order.
+    pub fn worlds(&self) -> Vec<(i64, i64, &World)> {
+        self.systems()
+            .into_iter()
+            .filter_map(|(c, r, v)| Some((c, r, v.world.as_ref()?)))
+            .collect()
+    }
+
+    /// Every occupied volume with its 1-based (col, row) hex, in row-major order.
+    pub fn systems(&self) -> Vec<(i64, i64, &Volume)> {
+        let mut out = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if let Some(v) = self.volumes[row][col].as_ref().filter(|v| !v.is_empty()) {
+                    out.push(((col + 1) as i64, (row + 1) as i64, v));
+                }
+            }
+        }
+        out
+    }
+
+    /// The mainworld at a "CCRR" hex, if the hex is in range and holds one.
+    pub fn world_at(&self, hex: &str) -> Option<&World> {
+        let (col, row) = parse_hex(hex)?;
+        self.get_volume((row - 1) as usize, (col - 1) as usize)?.world.as_ref()
+    }
+
     pub fn system_count(&self) -> usize {
         self.volumes.iter()
             .flat_map(|row| row.iter())
@@ -156,8 +264,15 @@ impl Sector {
             output.push_str(&format!("# Ruleset: {}\n", self.ruleset_title));
         }
         output.push_str("# 32 columns x 40 rows\n");
-        output.push_str("Location UWP       Temp Bases TC          Factions     Stars         Orbits        Name\n");
-        output.push_str("-------- --------- ---- ----- ----------- ------------ ------------- ------------- ----\n");
+        let width = runtime::ruleset().uwp_width();
+        output.push_str(&format!(
+            "Location {:<width$} Temp Bases TC          Factions     Stars         Orbits        Name\n",
+            "UWP"
+        ));
+        output.push_str(&format!(
+            "-------- {} ---- ----- ----------- ------------ ------------- ------------- ----\n",
+            "-".repeat(width)
+        ));
         
         // Process by subsector (4x4 grid of 8x10 subsectors)
         for subsector_row in 0..4 {
@@ -173,7 +288,7 @@ impl Sector {
                         
                         if let Some(volume) = self.get_volume(row, col) {
                             if !volume.is_empty() {
-                                output.push_str(&volume.to_ascii());
+                                output.push_str(&volume.to_ascii_at(self.thrust));
                                 output.push('\n');
                             }
                         }
@@ -181,7 +296,14 @@ impl Sector {
                 }
             }
         }
-        
+
+        if !self.mains.is_empty() {
+            output.push_str("\n# Mains (jump-1 chains)\n");
+            for m in &self.mains {
+                output.push_str(&format!("# {} ({}): {}\n", m.name, m.hexes.len(), m.hexes.join(" ")));
+            }
+        }
+
         output
     }
 }
@@ -207,4 +329,16 @@ mod tests {
         assert!(s.get_volume(0, 0).is_some(), "clustered systems should survive");
         assert!(s.get_volume(29, 19).is_none(), "isolated system should be pruned");
     }
+
+    #[test]
+    fn json_round_trip() {
+        let mut s = Sector::new("t".into(), 32, 40);
+        let mut v = Volume::new(4, 2);
+        v.world = Some(World::new(4, 2));
+        s.set_volume(4, 2, v);
+        let back: Sector = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+        assert_eq!((back.width, back.height), (32, 40));
+        assert!(back.get_volume(4, 2).is_some());
+        assert_eq!(back.worlds().len(), 1);
+    }
 }
@@ -21,6 +21,23 @@ impl fmt::Display for StarType {
     }
 }
 
+impl StarType {
+    /// Parse a spectral letter as the ruleset's `stars:` tables write it.
+    pub fn from_code(code: &str) -> Option<StarType> {
+        Some(match code {
+            "O" => StarType::O,
+            "B" => StarType::B,
+            "A" => StarType::A,
+            "F" => StarType::F,
+            "G" => StarType::G,
+            "K" => StarType::K,
+            "M" => StarType::M,
+            "D" => StarType::D,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum StarSize {
     Ia,  // Bright supergiant
@@ -48,6 +65,23 @@ impl fmt::Display for StarSize {
     }
 }
 
+impl StarSize {
+    /// Parse a luminosity class (Ia, Ib, II .. VI, D).
+    pub fn from_code(code: &str) -> Option<StarSize> {
+        Some(match code {
+            "Ia" => StarSize::Ia,
+            "Ib" => StarSize::Ib,
+            "II" => StarSize::II,
+            "III" => StarSize::III,
+            "IV" => StarSize::IV,
+            "V" => StarSize::V,
+            "VI" => StarSize::VI,
+            "D" => StarSize::D,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Star {
     pub star_type: StarType,
@@ -106,8 +140,25 @@ impl Star {
         let outer = 1.35 * self.luminosity.sqrt();
         (inner, outer)
     }
-    
-    pub fn to_string(&self) -> String {
-        format!("{}{}", self.spectral, self.star_size)
+
+    /// Radius in solar radii, from L = R²T⁴ relative to the Sun. Degenerate stars
+    /// come out smaller than they are, so floor at roughly Earth's size.
+    pub fn radius(&self) -> f64 {
+        let t = 5800.0 / self.temperature.max(1) as f64;
+        (self.luminosity.sqrt() * t * t).max(0.01)
+    }
+
+    /// The 100-diameter jump limit, in AU.
+    pub fn jump_limit(&self) -> f64 {
+        200.0 * self.radius() * SOLAR_RADIUS_KM / AU_KM
+    }
+}
+
+pub const SOLAR_RADIUS_KM: f64 = 695_700.0;
+pub const AU_KM: f64 = 149_597_871.0;
+
+impl fmt::Display for Star {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.spectral, self.star_size)
     }
 }
\ No newline at end of file
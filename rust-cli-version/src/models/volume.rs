@@ -2,6 +2,36 @@ use serde::{Deserialize, Serialize};
 use crate::models::{World, Star, OrbitContent};
 use crate::models::orbit::Orbit;
 use crate::models::world::ehex;
+use crate::models::star::AU_KM;
+use crate::rules::{runtime, Context, Value};
+
+/// Where a ship can jump from in a system: the 100-diameter limits of its stars and
+/// of the mainworld, and the nearest safe point to the mainworld.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpShadow {
+    pub stars: Vec<(String, f64)>, // (star, 100D limit in AU), primary first
+    pub world_km: f64,             // the mainworld's 100D limit; 0 for size 0
+    pub orbit_au: f64,             // the mainworld's distance from the primary
+    pub in_shadow: bool,           // the mainworld orbits inside some star's limit
+    pub jump_km: f64,              // mainworld to the nearest jump point
+}
+
+impl JumpShadow {
+    /// Hours from the jump point to the mainworld at `thrust` G, accelerating to the
+    /// midpoint and braking the rest of the way.
+    pub fn travel_hours(&self, thrust: f64) -> f64 {
+        2.0 * (self.jump_km * 1000.0 / (thrust * 9.81)).sqrt() / 3600.0
+    }
+}
+
+/// A distance in km when it is under a tenth of an AU, else in AU.
+fn distance(km: f64) -> String {
+    if km < 0.1 * AU_KM {
+        format!("{km:.0} km")
+    } else {
+        format!("{:.2} au", km / AU_KM)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Volume {
@@ -31,6 +61,54 @@ impl Volume {
         format!("{:02}{:02}", self.col + 1, self.row + 1)
     }
 
+    /// Belts and gas giants orbiting the primary (the B and G of PBG).
+    pub fn belts_and_giants(&self) -> (usize, usize) {
+        let orbits = self.star.iter().flat_map(|s| s.orbits.iter());
+        orbits.fold((0, 0), |(b, g), o| match o {
+            OrbitContent::Belt(_) => (b + 1, g),
+            OrbitContent::GasGiant(_) => (b, g + 1),
+            _ => (b, g),
+        })
+    }
+
+    /// The system's jump shadows. Needs a star and a mainworld; a mainworld found in
+    /// no orbit (a satellite, or an older save) is taken to sit in the biozone.
+    /// Companions sit at their orbit's distance from the primary, lined up with the
+    /// mainworld (the worst case), so a near companion can shadow it too.
+    pub fn jump_shadow(&self) -> Option<JumpShadow> {
+        let (star, world) = (self.star.as_ref()?, self.world.as_ref()?);
+        let mut stars = vec![(star.to_string(), star.jump_limit())];
+        stars.extend(star.companions.iter().map(|c| (c.to_string(), c.jump_limit())));
+        let mut at = vec![0.0];
+        at.extend(star.companions.iter().map(|c| star.orbit_to_au(c.orbit_number)));
+        let orbit_au = star
+            .orbits
+            .iter()
+            .find_map(|o| match o {
+                OrbitContent::World(w) => Some(w.au),
+                _ => None,
+            })
+            .unwrap_or_else(|| {
+                let (inner, outer) = star.biozone();
+                (inner + outer) / 2.0
+            });
+        let world_km = 100.0 * world.size as f64 * 1600.0;
+        // How far the mainworld must travel to clear each star's limit; 0 when outside.
+        let clear_au = stars
+            .iter()
+            .zip(&at)
+            .map(|((_, limit), au)| (limit - (orbit_au - au).abs()).max(0.0))
+            .fold(0.0, f64::max);
+        let in_shadow = clear_au > 0.0;
+        let star_km = clear_au * AU_KM;
+        Some(JumpShadow { stars, world_km, orbit_au, in_shadow, jump_km: world_km.max(star_km) })
+    }
+
+    /// Hours from the jump point to the mainworld at `thrust` G.
+    pub fn jump_travel_hours(&self, thrust: f64) -> Option<f64> {
+        self.jump_shadow().map(|s| s.travel_hours(thrust))
+    }
+
     /// One T5 Second Survey row (tab-delimited). Mirrors Ruby/Go Volume#to_tab.
     pub fn to_tab(&self, sector_name: &str, allegiance: &str) -> String {
         let w = match &self.world {
@@ -55,11 +133,7 @@ impl Volume {
         }
         let pbg = format!("{}{}{}", w.pop_multiplier, belts.min(9), gg.min(9));
 
-        let zone = if (w.government == 0 && w.law_level == 0) || w.law_level >= 9 {
-            "A"
-        } else {
-            ""
-        };
+        let zone = w.travel_zone();
 
         let stars = if let Some(s) = &self.star {
             let mut v = vec![s.to_string()];
@@ -119,7 +193,13 @@ impl Volume {
     }
 
     pub fn to_ascii(&self) -> String {
+        self.to_ascii_at(1.0)
+    }
+
+    /// The system detail, with jump-point travel times at `thrust` G.
+    pub fn to_ascii_at(&self, thrust: f64) -> String {
         if let Some(world) = &self.world {
+            let width = runtime::ruleset().uwp_width();
             let stars_str = if let Some(star) = &self.star {
                 star.to_string()
             } else {
@@ -143,11 +223,11 @@ impl Volume {
                         } else {
                             " "
                         };
-                        format!("\n  -- {:2}. {} {} // {:9} // {:4.1} au",
+                        format!("\n  -- {:2}. {} {} // {:width$} // {:4.1} au",
                             i + 1,
                             bio,
                             o.to_ascii(),
-                            Self::orbit_uwp(o),
+                            Self::orbit_uwp(o, width),
                             o.au()
                         )
                     })
@@ -183,7 +263,7 @@ impl Volume {
             
             // Format with proper spacing/alignment
             let coords_padded = format!("{:<8}", self.coords());
-            let uwp_padded = format!("{:<9}", world.uwp);  
+            let uwp_padded = format!("{:<width$}", world.uwp);
             let temp_padded = format!("{:<4}", world.temperature.to_code());
             let bases_padded = format!("{:<5}", world.bases_string());
             let trade_codes_padded = format!("{:<11}", world.trade_codes_string());
@@ -202,11 +282,31 @@ impl Volume {
                 name_ext.push_str("  ");
                 name_ext.push_str(&world.native);
             }
+            if let Some(f) = &world.facilities {
+                name_ext.push_str(&format!("\n  ^^ Starport {}: {}", world.starport, f.describe()));
+            }
+            if let Some(c) = &world.culture {
+                name_ext.push_str("\n  ~~ ");
+                name_ext.push_str(&c.describe());
+            }
+            if let Some(js) = self.jump_shadow() {
+                let stars: Vec<String> =
+                    js.stars.iter().map(|(s, au)| format!("{s} {au:.2} au")).collect();
+                name_ext.push_str(&format!(
+                    "\n  %% 100D: {}; world {}; jump point {}{}, {:.1}h at {}G",
+                    stars.join(", "),
+                    distance(js.world_km),
+                    distance(js.jump_km),
+                    if js.in_shadow { " (star's shadow)" } else { "" },
+                    js.travel_hours(thrust),
+                    thrust
+                ));
+            }
 
             format!(
                 "{} {} {} {} {} {} {} {} {}{}",
                 coords_padded,      // Left-align in 8-character field
-                uwp_padded,         // Left-align UWP in 13-character field (11 + 2 padding)
+                uwp_padded,         // Left-align UWP in a field as wide as the ruleset's UWP
                 temp_padded,        // Left-align in 4-character field
                 bases_padded,       // Left-align in 5-character field
                 trade_codes_padded, // Left-align in 11-character field
@@ -224,25 +324,112 @@ impl Volume {
         }
     }
     
-    fn orbit_uwp(orbit: &crate::models::OrbitContent) -> String {
+    fn orbit_uwp(orbit: &crate::models::OrbitContent, width: usize) -> String {
         use crate::models::OrbitContent;
         match orbit {
-            OrbitContent::Empty(_) => ".........".to_string(),
+            OrbitContent::Empty(_) => ".".repeat(width),
             OrbitContent::World(w) => w.world.uwp.clone(),
             OrbitContent::GasGiant(g) => {
                 match g.size {
-                    crate::models::orbit::GiantSize::Small => "Small GG ".to_string(),
-                    crate::models::orbit::GiantSize::Large => "Large GG ".to_string(),
+                    crate::models::orbit::GiantSize::Small => "Small GG".to_string(),
+                    crate::models::orbit::GiantSize::Large => "Large GG".to_string(),
                 }
             },
-            OrbitContent::Belt(_) => "Belt     ".to_string(),
+            OrbitContent::Belt(_) => "Belt".to_string(),
             OrbitContent::Hostile(h) => {
-                format!("X..{:X}{:X}...-.",
-                    h.atmosphere.min(15),
-                    h.hydrographics.min(15)
-                )
+                let ctx = Context::from([
+                    ("port".to_string(), Value::from_str("X")),
+                    ("atmo".to_string(), Value::from_int(h.atmosphere)),
+                    ("hydro".to_string(), Value::from_int(h.hydrographics)),
+                ]);
+                runtime::ruleset().uwp_code(&ctx)
+            },
+            OrbitContent::Rockball(_) => {
+                runtime::ruleset().uwp_code(&Context::from([("port".to_string(), Value::from_str("Y"))]))
             },
-            OrbitContent::Rockball(_) => "Y......-.".to_string(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::orbit::WorldOrbit;
+    use crate::models::{StarSize, StarType};
+
+    fn system(au: f64) -> Volume {
+        let mut world = World::new(0, 0);
+        world.size = 8;
+        let mut star = Star::new(StarType::G, StarSize::V, true);
+        star.orbits.push(OrbitContent::World(WorldOrbit { orbit_number: 2, au, world: world.clone() }));
+        let mut v = Volume::new(0, 0);
+        v.world = Some(world);
+        v.star = Some(star);
+        v
+    }
+
+    #[test]
+    fn jump_shadows_and_travel_time() {
+        let clear = system(1.2).jump_shadow().unwrap();
+        assert!((clear.stars[0].1 - 0.93).abs() < 0.01, "Sun-like 100D is ~0.93 au");
+        assert!(!clear.in_shadow);
+        assert_eq!((clear.world_km, clear.jump_km), (1_280_000.0, 1_280_000.0));
+        assert!((clear.travel_hours(1.0) - 6.35).abs() < 0.01);
+        assert!((clear.travel_hours(4.0) - 6.35 / 2.0).abs() < 0.01);
+
+        let shadowed = system(0.4).jump_shadow().unwrap();
+        assert!(shadowed.in_shadow);
+        assert!((shadowed.jump_km / AU_KM - 0.53).abs() < 0.01, "out to the star's limit");
+        let v = system(0.4);
+        assert_eq!(v.jump_travel_hours(2.0), Some(shadowed.travel_hours(2.0)));
+        assert!(v.to_ascii_at(2.0).contains("(star's shadow)"));
+        assert!(Volume::new(0, 0).jump_shadow().is_none());
+    }
+
+    #[test]
+    fn ascii_columns_follow_the_uwp_width() {
+        use crate::models::orbit::{EmptyOrbit, GasGiant, GiantSize};
+        use crate::rules::Ruleset;
+        let layout = "uwp_layout: [port, size, atmo, hydro, \"if(temp=='H', 2, 1)\", pop, gov, law, \"-\", tech]";
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap().overlay(serde_yaml::from_str(layout).unwrap()).unwrap());
+        let mut v = system(1.2);
+        v.world.as_mut().unwrap().update_uwp();
+        let star = v.star.as_mut().unwrap();
+        star.orbits.push(OrbitContent::Empty(EmptyOrbit { orbit_number: 3, au: 1.6 }));
+        let giant = GasGiant { orbit_number: 4, au: 2.8, size: GiantSize::Small, moons: Vec::new() };
+        star.orbits.push(OrbitContent::GasGiant(giant));
+
+        let text = v.to_ascii_at(1.0);
+        let uwp = &v.world.as_ref().unwrap().uwp;
+        assert_eq!(uwp.len(), 10);
+        assert!(text.starts_with(&format!("0101     {uwp} ")), "{text}");
+        let rows: Vec<&str> = text.lines().filter(|l| l.starts_with("  --")).collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].contains(" // .......... // "), "{}", rows[1]);
+        assert!(rows[2].contains(" // Small GG   // "), "{}", rows[2]);
+        let ends: Vec<usize> = rows.iter().map(|r| r.rfind(" // ").unwrap()).collect();
+        assert!(ends.iter().all(|&e| e == ends[0]), "{rows:?}");
+    }
+
+    #[test]
+    fn companions_cast_shadows() {
+        // A giant companion at orbit 2 (1.2 au) swallows a mainworld the primary leaves clear.
+        let mut v = system(1.2);
+        let star = v.star.as_mut().unwrap();
+        let mut giant = Star::new(StarType::K, StarSize::III, false);
+        giant.luminosity = 100.0;
+        giant.temperature = 4000;
+        giant.orbit_number = 2;
+        let limit = giant.jump_limit();
+        star.companions.push(giant);
+        let js = v.jump_shadow().unwrap();
+        assert_eq!(js.stars.len(), 2);
+        assert!(js.in_shadow);
+        assert!((js.jump_km - limit * AU_KM).abs() < 1.0, "out to the companion's limit");
+
+        // The same companion far out (orbit 10, ~307 au) leaves it clear.
+        v.star.as_mut().unwrap().companions[0].orbit_number = 10;
+        let js = v.jump_shadow().unwrap();
+        assert!(!js.in_shadow);
+        assert_eq!(js.jump_km, js.world_km);
+    }
+}
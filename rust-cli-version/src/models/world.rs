@@ -1,16 +1,12 @@
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
 
-/// Traveller extended hex (skips I and O), matching the Ruby/Go implementations.
-const EHEX: &[u8] = b"0123456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+use crate::rules::{runtime, Context, Value};
 
+/// A digit in the active ruleset's `hex` alphabet (T5's extended hex skips I and O).
 pub fn ehex(n: u8) -> char {
-    let i = n as usize;
-    if i < EHEX.len() {
-        EHEX[i] as char
-    } else {
-        EHEX[EHEX.len() - 1] as char
-    }
+    runtime::ruleset().hex_digit(n as i64)
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -48,6 +44,93 @@ pub struct World {
     pub native: String,
     #[serde(default)]
     pub pop_multiplier: u8, // 1-9 (0 if unpopulated); the P in PBG
+
+    // World Trade Number from the ruleset's `trade.wtn` formula.
+    #[serde(default)]
+    pub wtn: i64,
+
+    // Starport facilities from the ruleset's `starport.facilities` table.
+    #[serde(default)]
+    pub facilities: Option<Facilities>,
+
+    // Military profile from the ruleset's `military:` section (None when unpopulated).
+    #[serde(default)]
+    pub military: Option<Military>,
+
+    // Culture from the ruleset's `culture:` tables (None when unpopulated).
+    #[serde(default)]
+    pub culture: Option<Culture>,
+}
+
+/// What a starport class offers at this world's tech level. Berthing is Cr per stay.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Facilities {
+    pub fuel: String,
+    pub shipyard: String,
+    pub repair: String,
+    pub berthing: i64,
+    pub highport: bool,
+    pub downport: bool,
+}
+
+impl Facilities {
+    /// One-line summary for the system listing and the SVG tooltip.
+    pub fn describe(&self) -> String {
+        let ports: Vec<&str> = [(self.highport, "highport"), (self.downport, "downport")]
+            .iter()
+            .filter(|(has, _)| *has)
+            .map(|(_, n)| *n)
+            .collect();
+        format!(
+            "fuel: {}; shipyard: {}; repair: {}; berthing Cr{}; {}",
+            self.fuel,
+            self.shipyard,
+            self.repair,
+            self.berthing,
+            if ports.is_empty() { "no port".to_string() } else { ports.join(" + ") }
+        )
+    }
+}
+
+/// Planetary defence and armed-forces budget. Budgets are MCr per year.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Military {
+    pub defence: i64,
+    pub budget: i64,
+    pub navy_budget: i64,
+    pub army_budget: i64,
+    pub fleet_tonnage: i64,
+    pub max_ship_tl: i64,
+    pub squadrons: i64, // stationed at the naval base (0 without one)
+}
+
+/// Religions, languages, customs, and the attitude to offworlders, read from Cx.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Culture {
+    pub offworlders: String,
+    pub religions: Vec<String>,
+    pub languages: Vec<String>,
+    pub quirks: Vec<String>,
+}
+
+impl Culture {
+    /// One-line summary for the system listing.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.offworlders.is_empty() {
+            parts.push(format!("{} to offworlders", self.offworlders));
+        }
+        for (label, list) in [
+            ("faith", &self.religions),
+            ("speech", &self.languages),
+            ("customs", &self.quirks),
+        ] {
+            if !list.is_empty() {
+                parts.push(format!("{label}: {}", list.join(", ")));
+            }
+        }
+        parts.join("; ")
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -85,11 +168,28 @@ pub enum Base {
     PirateBase,
     Depot,
     Way,
+    Custom(char), // a ruleset-declared kind, by its `code:` letter
 }
 
 impl Base {
-    pub fn to_code(&self) -> &'static str {
-        match self {
+    /// The variant for a ruleset base kind; unknown kinds need their `code:` letter.
+    pub fn from_kind(kind: &str, code: Option<char>) -> Option<Base> {
+        Some(match kind {
+            "naval" => Base::Naval,
+            "scout" => Base::Scout,
+            "research" => Base::Research,
+            "tas" => Base::TAS,
+            "imperial" | "consulate" => Base::Imperial,
+            "pirate" => Base::PirateBase,
+            "depot" => Base::Depot,
+            "way" => Base::Way,
+            _ => Base::Custom(code?),
+        })
+    }
+
+    /// The report letter; only a ruleset-declared kind allocates.
+    pub fn to_code(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
             Base::Naval => "N",
             Base::Scout => "S",
             Base::Research => "R",
@@ -98,6 +198,22 @@ impl Base {
             Base::PirateBase => "P",
             Base::Depot => "D",
             Base::Way => "W",
+            Base::Custom(c) => return Cow::Owned(c.to_string()),
+        })
+    }
+
+    /// Default SVG glyph (a ruleset `glyph:` overrides it).
+    pub fn glyph(&self) -> String {
+        match self {
+            Base::Naval => "⚓".into(),
+            Base::Scout => "⚜".into(),
+            Base::Research => "⚗".into(),
+            Base::TAS => "✦".into(),
+            Base::Imperial => "♛".into(),
+            Base::PirateBase => "☠".into(),
+            Base::Depot => "◆".into(),
+            Base::Way => "✉".into(),
+            Base::Custom(c) => c.to_string(),
         }
     }
 }
@@ -129,6 +245,10 @@ impl World {
             ru: 0,
             native: String::new(),
             pop_multiplier: 0,
+            wtn: 0,
+            facilities: None,
+            military: None,
+            culture: None,
         }
     }
 
@@ -136,18 +256,26 @@ impl World {
         format!("{:02}{:02}", self.col + 1, self.row + 1)
     }
 
+    /// Render `uwp` through the active ruleset's `uwp_layout`.
     pub fn update_uwp(&mut self) {
-        self.uwp = format!(
-            "{}{}{}{}{}{}{}-{}",
-            self.starport,
-            ehex(self.size),
-            ehex(self.atmosphere),
-            ehex(self.hydrographics),
-            ehex(self.population),
-            ehex(self.government),
-            ehex(self.law_level),
-            ehex(self.tech_level)
-        );
+        self.uwp = runtime::ruleset().uwp_code(&self.context());
+    }
+
+    /// The finished world as expression variables (`size`, `port`, `temp`, ...).
+    pub fn context(&self) -> Context {
+        let digits = [
+            ("size", self.size),
+            ("atmo", self.atmosphere),
+            ("hydro", self.hydrographics),
+            ("pop", self.population),
+            ("gov", self.government),
+            ("law", self.law_level),
+            ("tech", self.tech_level),
+        ];
+        let mut ctx: Context = digits.iter().map(|(k, v)| (k.to_string(), Value::from_int(*v))).collect();
+        ctx.insert("port".into(), Value::Str(self.starport.to_string()));
+        ctx.insert("temp".into(), Value::Str(self.temperature.to_code().to_string()));
+        ctx
     }
 
     pub fn bases_string(&self) -> String {
@@ -166,6 +294,16 @@ impl World {
         }
     }
 
+    /// Travel zone: "A" (Amber) for anarchies and extreme law levels, else "" (Green).
+    /// Nothing is zoned Red by generation; "R" is honoured where a zone is read.
+    pub fn travel_zone(&self) -> &'static str {
+        if (self.government == 0 && self.law_level == 0) || self.law_level >= 9 {
+            "A"
+        } else {
+            ""
+        }
+    }
+
     /// T5 extension block: { +Ix } (RLI±E) [HASS] RU:n (empty when not extended).
     pub fn extensions(&self) -> String {
         if !self.extended {
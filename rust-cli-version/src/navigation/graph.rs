@@ -0,0 +1,258 @@
+//! The jump graph: every system as a node carrying its world's attributes, and an
+//! edge between each pair within jump-N weighted by distance and BTN. Serialised
+//! into the sector JSON and exportable to Graphviz DOT and GraphML.
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::sector::hex_jump;
+use crate::models::Sector;
+use crate::trade::bilateral_trade_number;
+
+use super::mains::find;
+use super::route::fuel_source;
+
+/// One system and the attributes external tools most often filter on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JumpNode {
+    pub hex: String,
+    pub name: String,
+    pub uwp: String,
+    pub starport: char,
+    pub population: u8,
+    pub tech_level: u8,
+    pub zone: String,
+    pub bases: String,
+    pub trade_codes: Vec<String>,
+    pub gas_giant: bool,
+    pub fuel: Option<String>,
+    pub wtn: i64,
+}
+
+/// An undirected jump between two hexes, `from` < `to`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JumpEdge {
+    pub from: String,
+    pub to: String,
+    pub jump: i64,
+    pub btn: i64, // 0 unless both ends are populated
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct JumpGraph {
+    pub max_jump: i64,
+    pub nodes: Vec<JumpNode>,
+    pub edges: Vec<JumpEdge>,
+}
+
+impl JumpGraph {
+    /// Connect every pair of systems within `max_jump`. Call after trade numbers are
+    /// set (the builder does) so edges carry their BTN.
+    pub fn build(sector: &Sector, max_jump: i64) -> JumpGraph {
+        let systems = sector.systems();
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for (i, &(c1, r1, v)) in systems.iter().enumerate() {
+            let Some(w) = &v.world else { continue };
+            nodes.push(JumpNode {
+                hex: format!("{c1:02}{r1:02}"),
+                name: w.name.clone(),
+                uwp: w.uwp.clone(),
+                starport: w.starport,
+                population: w.population,
+                tech_level: w.tech_level,
+                zone: w.travel_zone().to_string(),
+                bases: w.bases.iter().map(|b| b.to_code()).collect(),
+                trade_codes: w.trade_codes.clone(),
+                gas_giant: w.gas_giant,
+                fuel: fuel_source(v).map(String::from),
+                wtn: w.wtn,
+            });
+            for &(c2, r2, u) in &systems[i + 1..] {
+                let Some(x) = &u.world else { continue };
+                let jump = hex_jump(c1, r1, c2, r2);
+                if jump > max_jump {
+                    continue;
+                }
+                let btn = if w.population > 0 && x.population > 0 {
+                    bilateral_trade_number(w, x, jump)
+                } else {
+                    0
+                };
+                let (a, b) = (format!("{c1:02}{r1:02}"), format!("{c2:02}{r2:02}"));
+                let (from, to) = if a < b { (a, b) } else { (b, a) };
+                edges.push(JumpEdge { from, to, jump, btn });
+            }
+        }
+        edges.sort_by(|x, y| (&x.from, &x.to).cmp(&(&y.from, &y.to)));
+        JumpGraph { max_jump, nodes, edges }
+    }
+
+    /// Hexes one jump of at most `jump` parsecs from `hex`, with the distance.
+    pub fn neighbours(&self, hex: &str, jump: i64) -> Vec<(&str, i64)> {
+        self.edges
+            .iter()
+            .filter(|e| e.jump <= jump)
+            .filter_map(|e| {
+                if e.from == hex {
+                    Some((e.to.as_str(), e.jump))
+                } else if e.to == hex {
+                    Some((e.from.as_str(), e.jump))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// A spanning forest of the graph (Kruskal). Nearest-neighbour: shortest jumps
+    /// first. Trade-weighted: highest BTN first, then shortest. Ties go by hex, so the
+    /// same sector always yields the same tree.
+    pub fn spanning_edges(&self, by_trade: bool) -> Vec<&JumpEdge> {
+        let mut order: Vec<&JumpEdge> = self.edges.iter().collect();
+        if by_trade {
+            order.sort_by_key(|e| (std::cmp::Reverse(e.btn), e.jump));
+        } else {
+            order.sort_by_key(|e| e.jump);
+        }
+        let index: std::collections::HashMap<&str, usize> =
+            self.nodes.iter().enumerate().map(|(i, n)| (n.hex.as_str(), i)).collect();
+        let mut parent: Vec<usize> = (0..self.nodes.len()).collect();
+        let mut tree = Vec::new();
+        for e in order {
+            let (Some(&a), Some(&b)) = (index.get(e.from.as_str()), index.get(e.to.as_str())) else {
+                continue;
+            };
+            let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+            if ra != rb {
+                parent[ra] = rb;
+                tree.push(e);
+            }
+        }
+        tree
+    }
+
+    /// Graphviz DOT: an undirected graph, nodes labelled name + hex + UWP, edges
+    /// carrying `jump`/`btn` (and `len`, so neato lays out by distance). Every string
+    /// is quoted and escaped; a ruleset's `uwp_layout` may print any separator.
+    pub fn to_dot(&self, name: &str) -> String {
+        let q = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = format!("graph \"{}\" {{\n  node [shape=box, fontsize=10];\n", q(name));
+        for n in &self.nodes {
+            let _ = writeln!(
+                out,
+                "  \"{}\" [label=\"{}\\n{} {}\", starport=\"{}\", population={}, tech_level={}, zone=\"{}\", bases=\"{}\", trade_codes=\"{}\", gas_giant={}, fuel=\"{}\", wtn={}];",
+                q(&n.hex), q(&n.name), q(&n.hex), q(&n.uwp), q(&n.starport.to_string()), n.population,
+                n.tech_level, q(&n.zone), q(&n.bases), q(&n.trade_codes.join(" ")), n.gas_giant,
+                q(n.fuel.as_deref().unwrap_or("")), n.wtn
+            );
+        }
+        for e in &self.edges {
+            let _ = writeln!(
+                out,
+                "  \"{}\" -- \"{}\" [jump={}, btn={}, len={}];",
+                q(&e.from), q(&e.to), e.jump, e.btn, e.jump
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// GraphML with typed keys for every node and edge attribute, strings escaped.
+    pub fn to_graphml(&self, name: &str) -> String {
+        let x = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&apos;")
+        };
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+        let keys = [
+            ("node", "name", "string"), ("node", "uwp", "string"), ("node", "starport", "string"),
+            ("node", "population", "int"), ("node", "tech_level", "int"), ("node", "zone", "string"),
+            ("node", "bases", "string"), ("node", "trade_codes", "string"),
+            ("node", "gas_giant", "boolean"), ("node", "fuel", "string"), ("node", "wtn", "int"),
+            ("edge", "jump", "int"), ("edge", "btn", "int"),
+        ];
+        for (on, key, ty) in keys {
+            let _ = writeln!(
+                out,
+                "  <key id=\"{key}\" for=\"{on}\" attr.name=\"{key}\" attr.type=\"{ty}\"/>"
+            );
+        }
+        let _ = writeln!(out, "  <graph id=\"{}\" edgedefault=\"undirected\">", x(name));
+        for n in &self.nodes {
+            let _ = writeln!(out, "    <node id=\"{}\">", x(&n.hex));
+            for (key, val) in [
+                ("name", x(&n.name)), ("uwp", x(&n.uwp)), ("starport", x(&n.starport.to_string())),
+                ("population", n.population.to_string()), ("tech_level", n.tech_level.to_string()),
+                ("zone", x(&n.zone)), ("bases", x(&n.bases)),
+                ("trade_codes", x(&n.trade_codes.join(" "))), ("gas_giant", n.gas_giant.to_string()),
+                ("fuel", x(n.fuel.as_deref().unwrap_or(""))), ("wtn", n.wtn.to_string()),
+            ] {
+                let _ = writeln!(out, "      <data key=\"{key}\">{val}</data>");
+            }
+            out.push_str("    </node>\n");
+        }
+        for e in &self.edges {
+            let _ = writeln!(
+                out,
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"jump\">{}</data><data key=\"btn\">{}</data></edge>",
+                x(&e.from), x(&e.to), e.jump, e.btn
+            );
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Volume, World};
+    use crate::rules::{runtime, Ruleset};
+
+    #[test]
+    fn graph_edges_and_exports() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("Spin & \"Marches\"".into(), 32, 40);
+        for (c, r, pop) in [(1, 1, 6), (2, 1, 7), (4, 1, 0), (9, 9, 5)] {
+            let mut w = World::new(0, 0);
+            w.name = format!("W{c}{r}");
+            w.uwp = format!("A<{c}&\"{r}");
+            w.trade_codes = vec!["Ag".into(), "<Hi>".into()];
+            w.population = pop;
+            w.wtn = 4;
+            let mut v = Volume::new(r - 1, c - 1);
+            v.world = Some(w);
+            s.set_volume(r - 1, c - 1, v);
+        }
+        let g = JumpGraph::build(&s, 3);
+        assert_eq!(g.nodes.len(), 4);
+        let pairs: Vec<_> = g.edges.iter().map(|e| (e.from.as_str(), e.to.as_str(), e.jump)).collect();
+        assert_eq!(pairs, [("0101", "0201", 1), ("0101", "0401", 3), ("0201", "0401", 2)]);
+        assert!(g.edges[0].btn > 0);
+        assert_eq!(g.edges[1].btn, 0, "unpopulated end");
+        assert_eq!(g.neighbours("0401", 2), [("0201", 2)]);
+
+        let near: Vec<_> = g.spanning_edges(false).iter().map(|e| e.to.as_str()).collect();
+        assert_eq!(near, ["0201", "0401"], "0101-0201 (J1), then 0201-0401 (J2)");
+        assert_eq!(g.spanning_edges(true).len(), 2);
+
+        let dot = g.to_dot(&s.name);
+        assert!(dot.starts_with("graph \"Spin & \\\"Marches\\\"\" {"));
+        assert!(dot.contains("\"0101\" -- \"0201\" [jump=1"));
+        assert!(dot.contains("label=\"W11\\n0101 A<1&\\\"1\""), "{dot}");
+        assert!(dot.contains("trade_codes=\"Ag <Hi>\""));
+        let ml = g.to_graphml(&s.name);
+        assert!(ml.contains("<graph id=\"Spin &amp; &quot;Marches&quot;\""));
+        assert_eq!(ml.matches("<edge ").count(), 3);
+        assert!(ml.contains("<data key=\"uwp\">A&lt;1&amp;&quot;1</data>"), "{ml}");
+        assert!(ml.contains("<data key=\"trade_codes\">Ag &lt;Hi&gt;</data>"));
+        assert!(!ml.contains("A<1"), "no raw markup in GraphML");
+    }
+}
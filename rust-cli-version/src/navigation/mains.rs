@@ -0,0 +1,97 @@
+//! T5 mains: chains of systems linked at jump-1, the backbone of jump-1 trade loops.
+//! A main is a jump-1 connected component of at least `min_size` systems; it is
+//! named for its most populous world. Separate from the SVG islands, which cluster
+//! at a configurable jump.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::sector::hex_jump;
+use crate::models::Sector;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Main {
+    pub name: String,
+    pub hexes: Vec<String>, // "CCRR", in hex order
+}
+
+/// Union-find root of `x`, halving the path on the way (mains, spanning forests).
+pub(crate) fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+/// Every jump-1 component of at least `min_size` systems, largest first.
+pub fn find_mains(sector: &Sector, min_size: usize) -> Vec<Main> {
+    let worlds = sector.worlds();
+    let mut parent: Vec<usize> = (0..worlds.len()).collect();
+    for i in 0..worlds.len() {
+        for j in (i + 1)..worlds.len() {
+            let ((c1, r1, _), (c2, r2, _)) = (worlds[i], worlds[j]);
+            if hex_jump(c1, r1, c2, r2) == 1 {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..worlds.len() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut mains: Vec<Main> = groups
+        .into_values()
+        .filter(|g| g.len() >= min_size.max(2))
+        .map(|g| {
+            let mut hexes: Vec<String> =
+                g.iter().map(|&i| format!("{:02}{:02}", worlds[i].0, worlds[i].1)).collect();
+            hexes.sort();
+            // Most populous world names the main; ties go to the lowest hex.
+            let top = g
+                .iter()
+                .map(|&i| worlds[i])
+                .max_by_key(|&(c, r, w)| (w.population, std::cmp::Reverse((c, r))))
+                .unwrap();
+            Main { name: format!("{} Main", top.2.name), hexes }
+        })
+        .collect();
+    mains.sort_by(|a, b| b.hexes.len().cmp(&a.hexes.len()).then(a.hexes[0].cmp(&b.hexes[0])));
+    mains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Volume, World};
+
+    #[test]
+    fn jump1_components_above_min() {
+        let mut s = Sector::new("t".into(), 32, 40);
+        let mut put = |c: usize, r: usize, name: &str, pop: u8| {
+            let mut w = World::new(0, 0);
+            w.name = name.into();
+            w.population = pop;
+            let mut v = Volume::new(r - 1, c - 1);
+            v.world = Some(w);
+            s.set_volume(r - 1, c - 1, v);
+        };
+        // A chain 0101-0201-0301-0401 at jump-1, plus a jump-2 neighbour that isn't on it.
+        put(1, 1, "Alpha", 5);
+        put(2, 1, "Beta", 9);
+        put(3, 1, "Gamma", 3);
+        put(4, 1, "Delta", 9);
+        put(6, 1, "Far", 8);
+        put(10, 10, "Pair1", 1);
+        put(10, 11, "Pair2", 1);
+
+        let mains = find_mains(&s, 3);
+        assert_eq!(mains.len(), 1);
+        assert_eq!(mains[0].hexes, ["0101", "0201", "0301", "0401"]);
+        assert_eq!(mains[0].name, "Beta Main", "most populous, lowest hex on a tie");
+        assert_eq!(find_mains(&s, 2).len(), 2, "the pair counts at min 2");
+    }
+}
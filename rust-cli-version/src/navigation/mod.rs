@@ -0,0 +1,14 @@
+//! Navigation over a generated sector — the jump graph, T5 mains, the X-boat
+//! network, and jump itineraries for a ship of a given jump rating, refuelling
+//! only where fuel is to be had. Call after the sector is built (and pruned); fuel
+//! prices come from the active ruleset's `fuel:` section.
+
+pub mod graph;
+pub mod mains;
+pub mod route;
+pub mod xboat;
+
+pub use graph::{JumpEdge, JumpGraph, JumpNode};
+pub use mains::{find_mains, Main};
+pub use route::{find_route, fuel_source, Itinerary, Leg, RouteOptions};
+pub use xboat::{build_network, mark_way_stations, travellermap_metadata, XboatLink, XboatNetwork};
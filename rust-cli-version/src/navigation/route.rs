@@ -0,0 +1,271 @@
+//! Shortest jump itinerary between two hexes. Each jump is a week in jumpspace; every
+//! intermediate stop must sell fuel or offer a gas giant or belt to skim, so the ship
+//! can refill before the next jump. Systems with no mainworld count too when they
+//! have one to skim. Ties on jumps break on the optional A/B-port preference, then on
+//! fuel cost.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::sector::{hex_jump, parse_hex};
+use crate::models::{Sector, Volume};
+use crate::rules::runtime;
+
+/// Ship and routing preferences for `find_route`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteOptions {
+    pub jump: i64, // jump rating, parsecs per jump
+    pub hull: i64, // displacement tons; sizes the fuel bill
+    pub avoid_amber: bool,
+    pub avoid_red: bool,
+    pub prefer_ab: bool, // favour stops with class A or B starports
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        RouteOptions { jump: 2, hull: 200, avoid_amber: false, avoid_red: false, prefer_ab: false }
+    }
+}
+
+/// One jump of an itinerary; `fuel` is where the tanks were filled before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leg {
+    pub from: String,
+    pub to: String,
+    pub parsecs: i64,
+    pub fuel: String, // refined | unrefined | wilderness | carried (the ship set out full)
+    pub tons: i64,
+    pub cost: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Itinerary {
+    pub legs: Vec<Leg>,
+    pub weeks: i64,
+    pub parsecs: i64,
+    pub fuel_tons: i64,
+    pub fuel_cost: i64,
+}
+
+/// The fuel a system offers: the starport's (`refined`/`unrefined`, from its
+/// facilities, else by class) or `wilderness` skimming from a gas giant or belt.
+/// A system with no mainworld has no port, but may still be skimmed.
+pub fn fuel_source(v: &Volume) -> Option<&'static str> {
+    let port = match &v.world {
+        Some(w) => match &w.facilities {
+            Some(f) => f.fuel.as_str(),
+            None => match w.starport {
+                'A' | 'B' => "refined",
+                'C' | 'D' => "unrefined",
+                _ => "none",
+            },
+        },
+        None => "none",
+    };
+    match port {
+        "refined" => Some("refined"),
+        "unrefined" => Some("unrefined"),
+        _ => {
+            let (belts, giants) = v.belts_and_giants();
+            let skim = v.world.as_ref().is_some_and(|w| w.gas_giant || w.size == 0);
+            (skim || giants > 0 || belts > 0).then_some("wilderness")
+        }
+    }
+}
+
+/// Every system in the sector with its 1-based (col, row) hex, worldless ones
+/// included (they are stops when they can be skimmed).
+fn route_nodes(sector: &Sector) -> Vec<(i64, i64, &Volume)> {
+    let hexes = (0..sector.height).flat_map(|row| (0..sector.width).map(move |col| (row, col)));
+    hexes
+        .filter_map(|(row, col)| Some(((col + 1) as i64, (row + 1) as i64, sector.get_volume(row, col)?)))
+        .collect()
+}
+
+/// Find the fewest-jumps itinerary from `from` to `to` ("CCRR" hexes).
+pub fn find_route(
+    sector: &Sector,
+    from: &str,
+    to: &str,
+    opts: &RouteOptions,
+) -> Result<Itinerary, String> {
+    let rs = runtime::ruleset();
+    let systems = route_nodes(sector);
+    let index = |hex: &str| -> Result<usize, String> {
+        let (c, r) = parse_hex(hex).ok_or_else(|| format!("bad hex {hex:?} (want CCRR)"))?;
+        systems
+            .iter()
+            .position(|&(sc, sr, _)| (sc, sr) == (c, r))
+            .ok_or_else(|| format!("no system at hex {hex}"))
+    };
+    let (start, goal) = (index(from)?, index(to)?);
+    let fuel: Vec<Option<&str>> = systems.iter().map(|(_, _, v)| fuel_source(v)).collect();
+    let stop_ok = |i: usize| -> bool {
+        if i == goal {
+            return true;
+        }
+        let zone = systems[i].2.world.as_ref().map_or("", |w| w.travel_zone());
+        fuel[i].is_some()
+            && !(opts.avoid_amber && zone == "A")
+            && !(opts.avoid_red && zone == "R")
+    };
+    let penalty = |i: usize| -> i64 {
+        let port = systems[i].2.world.as_ref().map_or('X', |w| w.starport);
+        (opts.prefer_ab && i != goal && !matches!(port, 'A' | 'B')) as i64
+    };
+    let tons = |parsecs: i64| opts.hull * parsecs * rs.fuel_per_parsec() / 100;
+    let bought = |i: usize| -> &str {
+        match fuel[i] {
+            Some(kind) => kind,
+            None => "carried",
+        }
+    };
+    let price = |i: usize| fuel[i].map_or(0, |k| rs.fuel_price(k));
+
+    // Dijkstra on (jumps, port penalty, fuel cost), lexicographic.
+    let n = systems.len();
+    let mut best = vec![(i64::MAX, i64::MAX, i64::MAX); n];
+    let mut prev = vec![usize::MAX; n];
+    let mut heap = BinaryHeap::new();
+    best[start] = (0, 0, 0);
+    heap.push(Reverse(((0, 0, 0), start)));
+    while let Some(Reverse((cost, i))) = heap.pop() {
+        if cost > best[i] {
+            continue;
+        }
+        if i == goal {
+            break;
+        }
+        let (c1, r1, _) = systems[i];
+        for (j, &(c2, r2, _)) in systems.iter().enumerate() {
+            let parsecs = hex_jump(c1, r1, c2, r2);
+            if j == i || parsecs > opts.jump || !stop_ok(j) {
+                continue;
+            }
+            let next = (cost.0 + 1, cost.1 + penalty(j), cost.2 + tons(parsecs) * price(i));
+            if next < best[j] {
+                best[j] = next;
+                prev[j] = i;
+                heap.push(Reverse((next, j)));
+            }
+        }
+    }
+    if prev[goal] == usize::MAX && goal != start {
+        return Err(format!("no jump-{} route from {from} to {to}", opts.jump));
+    }
+
+    let mut path = vec![goal];
+    while *path.last().unwrap() != start {
+        path.push(prev[*path.last().unwrap()]);
+    }
+    path.reverse();
+    let hex = |i: usize| format!("{:02}{:02}", systems[i].0, systems[i].1);
+    let legs: Vec<Leg> = path
+        .windows(2)
+        .map(|p| {
+            let parsecs = hex_jump(systems[p[0]].0, systems[p[0]].1, systems[p[1]].0, systems[p[1]].1);
+            Leg {
+                from: hex(p[0]),
+                to: hex(p[1]),
+                parsecs,
+                fuel: bought(p[0]).to_string(),
+                tons: tons(parsecs),
+                cost: tons(parsecs) * price(p[0]),
+            }
+        })
+        .collect();
+    Ok(Itinerary {
+        weeks: legs.len() as i64,
+        parsecs: legs.iter().map(|l| l.parsecs).sum(),
+        fuel_tons: legs.iter().map(|l| l.tons).sum(),
+        fuel_cost: legs.iter().map(|l| l.cost).sum(),
+        legs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::World;
+    use crate::rules::Ruleset;
+
+    fn put(s: &mut Sector, hex: &str, port: char, gas_giant: bool, law: u8) {
+        let (c, r) = parse_hex(hex).unwrap();
+        let mut w = World::new(0, 0);
+        w.starport = port;
+        w.size = 6;
+        w.government = 4;
+        w.law_level = law;
+        w.gas_giant = gas_giant;
+        let (row, col) = ((r - 1) as usize, (c - 1) as usize);
+        let mut v = Volume::new(row, col);
+        v.world = Some(w);
+        s.set_volume(row, col, v);
+    }
+
+    #[test]
+    fn refuels_only_where_fuel_is() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("t".into(), 32, 40);
+        put(&mut s, "0101", 'A', false, 4);
+        put(&mut s, "0301", 'X', true, 4); // skim the gas giant
+        put(&mut s, "0302", 'X', false, 4); // dry: never a stop
+        put(&mut s, "0501", 'X', false, 4);
+        let opts = RouteOptions::default();
+        let it = find_route(&s, "0101", "0501", &opts).unwrap();
+        let stops: Vec<_> = it.legs.iter().map(|l| l.to.as_str()).collect();
+        assert_eq!(stops, ["0301", "0501"]);
+        assert_eq!((it.weeks, it.parsecs, it.fuel_tons), (2, 4, 80));
+        assert_eq!(it.legs[0].fuel, "refined");
+        assert_eq!(it.fuel_cost, 40 * 500, "second leg is skimmed free");
+
+        let jump1 = RouteOptions { jump: 1, ..opts.clone() };
+        assert!(find_route(&s, "0101", "0501", &jump1).is_err());
+    }
+
+    #[test]
+    fn avoids_amber_and_prefers_good_ports() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("t".into(), 32, 40);
+        put(&mut s, "0101", 'A', false, 4);
+        put(&mut s, "0301", 'C', false, 9); // amber
+        put(&mut s, "0302", 'B', false, 4);
+        put(&mut s, "0501", 'X', false, 4);
+        let via = |o: &RouteOptions| find_route(&s, "0101", "0501", o).unwrap().legs[0].to.clone();
+        let base = RouteOptions::default();
+        assert_eq!(via(&base), "0301", "cheapest: no A/B preference, C-port sells unrefined");
+        assert_eq!(via(&RouteOptions { avoid_amber: true, ..base.clone() }), "0302");
+        assert_eq!(via(&RouteOptions { prefer_ab: true, ..base.clone() }), "0302");
+    }
+
+    #[test]
+    fn skims_at_worldless_systems() {
+        use crate::models::orbit::{GasGiant, GiantSize};
+        use crate::models::{OrbitContent, Star, StarSize, StarType};
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("t".into(), 32, 40);
+        put(&mut s, "0101", 'A', false, 4);
+        put(&mut s, "0501", 'X', false, 4);
+        let mut star = Star::new(StarType::M, StarSize::V, true);
+        let giant = GasGiant { orbit_number: 3, au: 1.6, size: GiantSize::Small, moons: vec![] };
+        star.orbits.push(OrbitContent::GasGiant(giant));
+        let mut v = Volume::new(0, 2);
+        v.star = Some(star.clone());
+        s.set_volume(0, 2, v);
+        assert_eq!(fuel_source(s.get_volume(0, 2).unwrap()), Some("wilderness"));
+
+        let it = find_route(&s, "0101", "0501", &RouteOptions::default()).unwrap();
+        let stops: Vec<_> = it.legs.iter().map(|l| l.to.as_str()).collect();
+        assert_eq!(stops, ["0301", "0501"]);
+        assert_eq!(it.legs[1].fuel, "wilderness");
+
+        // Without the giant the empty hex is no stop, and jump-2 cannot bridge the gap.
+        star.orbits.clear();
+        let mut v = Volume::new(0, 2);
+        v.star = Some(star);
+        s.set_volume(0, 2, v);
+        assert!(find_route(&s, "0101", "0501", &RouteOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! The X-boat courier network. Hubs are the worlds the ruleset's `xboat.hub` picks;
+//! they are joined by a minimum spanning tree over their fewest-jumps distances on the
+//! jump-`max_jump` graph, each tree edge laid down as its shortest chain of links.
+//! Every non-hub world on a chain is a way station.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::sector::hex_jump;
+use crate::models::world::Base;
+use crate::models::{Sector, World};
+use crate::rules::expr::{Context, Value};
+use crate::rules::runtime;
+
+/// One X-boat link, `from` < `to`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XboatLink {
+    pub from: String,
+    pub to: String,
+    pub jump: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct XboatNetwork {
+    pub hubs: Vec<String>,
+    pub way_stations: Vec<String>,
+    pub links: Vec<XboatLink>,
+}
+
+fn hub_ctx(w: &World) -> Context {
+    let mut ctx = w.context();
+    ctx.insert("ix".into(), Value::Int(w.ix));
+    for (k, b) in [("naval", Base::Naval), ("scout", Base::Scout), ("way", Base::Way), ("depot", Base::Depot)] {
+        ctx.insert(k.into(), Value::Int(w.bases.contains(&b) as i64));
+    }
+    ctx
+}
+
+/// Build the network over the sector's worlds.
+pub fn build_network(sector: &Sector) -> XboatNetwork {
+    let rs = runtime::ruleset();
+    let max_jump = rs.xboat_max_jump();
+    let worlds = sector.worlds();
+    let hex = |i: usize| format!("{:02}{:02}", worlds[i].0, worlds[i].1);
+    let n = worlds.len();
+    let adj: Vec<Vec<usize>> = (0..n)
+        .map(|i| {
+            (0..n)
+                .filter(|&j| {
+                    j != i
+                        && hex_jump(worlds[i].0, worlds[i].1, worlds[j].0, worlds[j].1) <= max_jump
+                })
+                .collect()
+        })
+        .collect();
+    let hubs: Vec<usize> = (0..n).filter(|&i| rs.xboat_hub(&hub_ctx(worlds[i].2))).collect();
+
+    // Fewest-links distance and predecessor from each hub (BFS).
+    let bfs = |start: usize| -> (Vec<i64>, Vec<usize>) {
+        let mut dist = vec![i64::MAX; n];
+        let mut prev = vec![usize::MAX; n];
+        let mut queue = VecDeque::from([start]);
+        dist[start] = 0;
+        while let Some(i) = queue.pop_front() {
+            for &j in &adj[i] {
+                if dist[j] == i64::MAX {
+                    dist[j] = dist[i] + 1;
+                    prev[j] = i;
+                    queue.push_back(j);
+                }
+            }
+        }
+        (dist, prev)
+    };
+    let trees: Vec<(Vec<i64>, Vec<usize>)> = hubs.iter().map(|&h| bfs(h)).collect();
+
+    // Prim's over the hubs; a hub unreachable from the tree starts a new one.
+    let mut in_tree = vec![false; hubs.len()];
+    let mut links: BTreeSet<(usize, usize)> = BTreeSet::new();
+    for seed in 0..hubs.len() {
+        if in_tree[seed] {
+            continue;
+        }
+        in_tree[seed] = true;
+        loop {
+            let mut best: Option<(i64, usize, usize)> = None;
+            for a in (0..hubs.len()).filter(|&a| in_tree[a]) {
+                for b in (0..hubs.len()).filter(|&b| !in_tree[b]) {
+                    let d = trees[a].0[hubs[b]];
+                    if d != i64::MAX && best.is_none_or(|(bd, _, _)| d < bd) {
+                        best = Some((d, a, b));
+                    }
+                }
+            }
+            let Some((_, a, b)) = best else { break };
+            in_tree[b] = true;
+            let mut at = hubs[b];
+            while at != hubs[a] {
+                let p = trees[a].1[at];
+                links.insert((p.min(at), p.max(at)));
+                at = p;
+            }
+        }
+    }
+
+    let on_net: BTreeSet<usize> = links.iter().flat_map(|&(a, b)| [a, b]).collect();
+    let mut net = XboatNetwork {
+        hubs: hubs.iter().map(|&h| hex(h)).collect(),
+        way_stations: on_net.iter().filter(|i| !hubs.contains(i)).map(|&i| hex(i)).collect(),
+        links: links
+            .iter()
+            .map(|&(a, b)| {
+                let (from, to) = if hex(a) < hex(b) { (hex(a), hex(b)) } else { (hex(b), hex(a)) };
+                let jump = hex_jump(worlds[a].0, worlds[a].1, worlds[b].0, worlds[b].1);
+                XboatLink { from, to, jump }
+            })
+            .collect(),
+    };
+    net.hubs.sort();
+    net.way_stations.sort();
+    net.links.sort_by(|x, y| (&x.from, &x.to).cmp(&(&y.from, &y.to)));
+    net
+}
+
+/// Mark each way station with a way-station base (W), as the Second Survey does.
+pub fn mark_way_stations(sector: &mut Sector, net: &XboatNetwork) {
+    for hex in &net.way_stations {
+        let Some((c, r)) = crate::models::sector::parse_hex(hex) else { continue };
+        let (row, col) = ((r - 1) as usize, (c - 1) as usize);
+        if let Some(w) = sector.volumes[row][col].as_mut().and_then(|v| v.world.as_mut()) {
+            if !w.bases.contains(&Base::Way) {
+                w.bases.push(Base::Way);
+            }
+        }
+    }
+}
+
+/// TravellerMap sector metadata (`<Sector>` XML) carrying the links as X-boat routes.
+pub fn travellermap_metadata(sector_name: &str, net: &XboatNetwork) -> String {
+    let name = sector_name
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Sector>\n  <Name>{name}</Name>\n  <Routes>\n"
+    );
+    for l in &net.links {
+        let _ = writeln!(out, "    <Route Start=\"{}\" End=\"{}\" Type=\"Xboat\" />", l.from, l.to);
+    }
+    out.push_str("  </Routes>\n</Sector>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Volume;
+    use crate::rules::Ruleset;
+
+    fn put(s: &mut Sector, c: usize, r: usize, ix: i64) {
+        let mut w = World::new(0, 0);
+        w.ix = ix;
+        w.starport = 'C';
+        let mut v = Volume::new(r - 1, c - 1);
+        v.world = Some(w);
+        s.set_volume(r - 1, c - 1, v);
+    }
+
+    #[test]
+    fn hubs_joined_through_way_stations() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("t".into(), 32, 40);
+        put(&mut s, 1, 1, 4); // hub
+        put(&mut s, 5, 1, 0); // relay
+        put(&mut s, 9, 1, 4); // hub, jump-8 from the first
+        put(&mut s, 9, 4, 0); // off the network
+        put(&mut s, 12, 1, 3); // hub, jump-3 from the second
+        let net = build_network(&s);
+        assert_eq!(net.hubs, ["0101", "0901", "1201"]);
+        assert_eq!(net.way_stations, ["0501"]);
+        let links: Vec<_> = net.links.iter().map(|l| (l.from.as_str(), l.to.as_str(), l.jump)).collect();
+        assert_eq!(links, [("0101", "0501", 4), ("0501", "0901", 4), ("0901", "1201", 3)]);
+
+        mark_way_stations(&mut s, &net);
+        assert_eq!(s.world_at("0501").unwrap().bases, [Base::Way]);
+        let xml = travellermap_metadata("Spin", &net);
+        assert!(xml.contains("<Route Start=\"0101\" End=\"0501\" Type=\"Xboat\" />"));
+    }
+}
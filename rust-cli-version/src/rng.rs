@@ -1,21 +1,61 @@
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use crate::error::{AstromapperError, Result};
 
 thread_local! {
-    static RNG: RefCell<Option<ChaCha8Rng>> = RefCell::new(None);
+    static RNG: RefCell<Option<ChaCha8Rng>> = const { RefCell::new(None) };
+    static SEED: Cell<u64> = const { Cell::new(0) };
+    static HEX: RefCell<String> = const { RefCell::new(String::new()) };
 }
 
 /// Initialize the thread-local RNG with a seed string
 pub fn init_rng(seed: &str) {
     let seed_value = string_to_seed(seed);
+    SEED.with(|s| s.set(seed_value));
     RNG.with(|r| {
         *r.borrow_mut() = Some(ChaCha8Rng::seed_from_u64(seed_value));
     });
 }
 
+/// A generator seeded from `key` alone, for rolls that must not touch (or depend
+/// on) the generation stream, e.g. a trade quote keyed by seed and route.
+pub fn keyed_rng(key: &str) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(string_to_seed(key))
+}
+
+/// Run `f` with `rng` as the dice source (every roll below, ruleset formulas
+/// included, draws on it), then put the thread's own stream back as it was.
+pub fn with_rng<T>(rng: ChaCha8Rng, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<ChaCha8Rng>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let saved = self.0.take();
+            RNG.with(|r| *r.borrow_mut() = saved);
+        }
+    }
+    let _restore = Restore(RNG.with(|r| r.borrow_mut().replace(rng)));
+    f()
+}
+
+/// Name the hex (CCRR) now being generated; `with_stream` keys its streams by it.
+pub fn set_hex(hex: &str) {
+    HEX.with(|h| *h.borrow_mut() = hex.to_string());
+}
+
+/// Run `f` on a stream of its own, derived from the generation seed, the current
+/// hex and `key` (what is rolled there, e.g. "military"). Optional extras roll
+/// this way, so adding, removing or editing one leaves the rest of the sector as
+/// the same seed always made it.
+pub fn with_stream<T>(key: &str, f: impl FnOnce() -> T) -> T {
+    let mut bytes = SEED.with(|s| s.get()).to_le_bytes().to_vec();
+    HEX.with(|h| bytes.extend_from_slice(h.borrow().as_bytes()));
+    bytes.push(b'/');
+    bytes.extend_from_slice(key.as_bytes());
+    with_rng(ChaCha8Rng::seed_from_u64(fnv1a64(&bytes)), f)
+}
+
 /// FNV-1a 64-bit hash over raw bytes. Unlike `DefaultHasher` (SipHash), this is
 /// guaranteed stable across Rust versions and platforms, so a given seed always
 /// maps to the same map. Identical to the Go and Ruby ports' derivation.
@@ -118,6 +158,24 @@ mod tests {
         assert_eq!(roll2, roll4);
     }
     
+    #[test]
+    fn test_streams_leave_generation_untouched() {
+        init_rng("TEST-SEED");
+        let plain = [roll_2d6().unwrap(), roll_2d6().unwrap()];
+
+        init_rng("TEST-SEED");
+        let first = roll_2d6().unwrap();
+        let rolls = || -> Vec<u32> { with_stream("extra", || (0..20).map(|_| roll_2d6().unwrap()).collect()) };
+        set_hex("0101");
+        let extra = rolls();
+        assert_eq!([first, roll_2d6().unwrap()], plain);
+
+        init_rng("TEST-SEED");
+        assert_eq!(rolls(), extra);
+        set_hex("0102");
+        assert_ne!(rolls(), extra);
+    }
+
     #[test]
     fn test_uninitialized_rng() {
         // Clear any previous RNG
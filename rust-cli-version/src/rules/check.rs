@@ -0,0 +1,349 @@
+//! Static checking of compiled ruleset expressions. `Node::eval` reads an unknown
+//! variable as 0 and never fails, so a typo (`hydor>=4`) or a string compared with a
+//! number silently never matches. Here each expression is walked once against the
+//! variables its step will see, tracking the range every number can take, to report
+//! undefined names, type mismatches, and conditions that are always true or false.
+
+use std::collections::BTreeMap;
+
+use super::expr::{Node, Value};
+
+/// What an expression can evaluate to: a number in `lo..=hi`, a condition that
+/// can be true and/or false, or a string (its value when a literal).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ty {
+    Int(i64, i64),
+    Bool(bool, bool), // (can be true, can be false)
+    Str(Option<String>),
+}
+
+impl Ty {
+    /// Any number at all.
+    pub const INT: Ty = Ty::Int(i64::MIN, i64::MAX);
+
+    /// A non-negative number (counts, budgets, distances).
+    pub const COUNT: Ty = Ty::Int(0, i64::MAX);
+
+    /// A 1/0 flag (bases, trade codes).
+    pub const FLAG: Ty = Ty::Int(0, 1);
+
+    pub fn str() -> Ty {
+        Ty::Str(None)
+    }
+
+    fn range(&self, what: &str) -> Result<(i64, i64), String> {
+        match self {
+            Ty::Int(lo, hi) => Ok((*lo, *hi)),
+            Ty::Bool(t, f) => Ok((if *f { 0 } else { 1 }, if *t { 1 } else { 0 })),
+            Ty::Str(_) => Err(format!("{what} on a string")),
+        }
+    }
+
+    fn cond(&self) -> Result<(bool, bool), String> {
+        match self {
+            Ty::Bool(t, f) => Ok((*t, *f)),
+            Ty::Int(..) => Err("a number used as a condition (always true); compare it, e.g. `x==1`".into()),
+            Ty::Str(_) => Err("a string used as a condition (always true); compare it, e.g. `x=='A'`".into()),
+        }
+    }
+}
+
+/// How a formula's result is used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Want {
+    Cond,   // must be a condition (evaluated with `is_true`)
+    Number, // a number; a condition counts as 1/0
+    Text,   // a string (a name, e.g. which table to roll on)
+    Digit,  // a UWP character: a number (as a hex digit) or a string, as-is
+}
+
+/// The variables an expression can see at its step, plus the names the pipeline
+/// only fills in later (reported as "not known yet" rather than undefined).
+#[derive(Clone, Debug, Default)]
+pub struct Scope {
+    vars: BTreeMap<String, Ty>,
+    pending: Vec<String>,
+}
+
+impl Scope {
+    pub fn new() -> Scope {
+        Scope::default()
+    }
+
+    pub fn with(mut self, name: &str, ty: Ty) -> Scope {
+        self.vars.insert(name.to_string(), ty);
+        self
+    }
+
+    pub fn set(&mut self, name: &str, ty: Ty) {
+        self.vars.insert(name.to_string(), ty);
+    }
+
+    pub fn pending(mut self, names: &[&str]) -> Scope {
+        self.pending.extend(names.iter().map(|n| n.to_string()));
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Result<Ty, String> {
+        if let Some(ty) = self.vars.get(name) {
+            return Ok(ty.clone());
+        }
+        if self.pending.iter().any(|p| p == name) {
+            return Err(format!("`{name}` is not known yet at this step"));
+        }
+        let known: Vec<&str> = self.vars.keys().map(String::as_str).collect();
+        if known.is_empty() {
+            Err(format!("undefined name `{name}` (no variables here)"))
+        } else {
+            Err(format!("undefined name `{name}` (known: {})", known.join(", ")))
+        }
+    }
+}
+
+/// Check one compiled expression used as `want` in `scope`.
+pub fn check(node: &Node, scope: &Scope, want: Want) -> Result<(), String> {
+    let ty = infer(node, scope)?;
+    match want {
+        Want::Cond => match ty.cond()? {
+            (true, false) => Err("condition is always true".into()),
+            (false, true) => Err("condition is always false".into()),
+            _ => Ok(()),
+        },
+        Want::Number => ty.range("expected a number, got a string").map(|_| ()),
+        Want::Text => match ty {
+            Ty::Str(_) => Ok(()),
+            _ => Err("expected a string, e.g. `'name'`".into()),
+        },
+        Want::Digit => Ok(()),
+    }
+}
+
+fn infer(node: &Node, scope: &Scope) -> Result<Ty, String> {
+    Ok(match node {
+        Node::Num(n) => Ty::Int(*n, *n),
+        Node::Str(s) => Ty::Str(Some(s.clone())),
+        Node::Var(k) => scope.lookup(k)?,
+        Node::Dice(n, sides) => {
+            let n = *n as i64;
+            Ty::Int(n, n.saturating_mul(*sides as i64))
+        }
+        Node::Flux => Ty::Int(-5, 5),
+        Node::Neg(x) => {
+            let (lo, hi) = infer(x, scope)?.range("negation")?;
+            Ty::Int(hi.saturating_neg(), lo.saturating_neg())
+        }
+        Node::Not(x) => {
+            let (t, f) = infer(x, scope)?.cond()?;
+            Ty::Bool(f, t)
+        }
+        Node::And(l, r) => {
+            let (lt, lf) = infer(l, scope)?.cond()?;
+            let (rt, rf) = infer(r, scope)?.cond()?;
+            Ty::Bool(lt && rt, lf || rf)
+        }
+        Node::Or(l, r) => {
+            let (lt, lf) = infer(l, scope)?.cond()?;
+            let (rt, rf) = infer(r, scope)?.cond()?;
+            Ty::Bool(lt || rt, lf && rf)
+        }
+        Node::Cmp(op, l, r) => compare(op, &infer(l, scope)?, &infer(r, scope)?)?,
+        Node::Arith(op, l, r) => {
+            let a = infer(l, scope)?.range("arithmetic")?;
+            let b = infer(r, scope)?.range("arithmetic")?;
+            let (lo, hi) = match op {
+                '+' => (a.0.saturating_add(b.0), a.1.saturating_add(b.1)),
+                '-' => (a.0.saturating_sub(b.1), a.1.saturating_sub(b.0)),
+                '*' => span(&[
+                    a.0.saturating_mul(b.0),
+                    a.0.saturating_mul(b.1),
+                    a.1.saturating_mul(b.0),
+                    a.1.saturating_mul(b.1),
+                ]),
+                _ if b.0 > 0 || b.1 < 0 => {
+                    let d = |x: i64, y: i64| x.checked_div(y).unwrap_or(i64::MAX);
+                    span(&[d(a.0, b.0), d(a.0, b.1), d(a.1, b.0), d(a.1, b.1)])
+                }
+                // A divisor that can be 0 (which yields 0) or ±1: anything up to |a|.
+                _ => span(&[0, a.0, a.1, a.0.saturating_neg(), a.1.saturating_neg()]),
+            };
+            Ty::Int(lo, hi)
+        }
+        Node::Call(f, args) => {
+            let a = args
+                .iter()
+                .map(|x| infer(x, scope)?.range(f))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (lo, hi) = match f.as_str() {
+                "min" => (a[0].0.min(a[1].0), a[0].1.min(a[1].1)),
+                "max" => (a[0].0.max(a[1].0), a[0].1.max(a[1].1)),
+                "clamp" => (a[0].0.max(a[1].0).min(a[2].0), a[0].1.max(a[1].1).min(a[2].1)),
+                _ if a[0].0 >= 0 => a[0],
+                _ if a[0].1 <= 0 => (a[0].1.saturating_neg(), a[0].0.saturating_neg()),
+                _ => (0, a[0].0.saturating_neg().max(a[0].1)),
+            };
+            Ty::Int(lo, hi)
+        }
+        Node::If(c, a, b) => {
+            let (t, f) = infer(c, scope)?.cond()?;
+            let (a, b) = (infer(a, scope)?, infer(b, scope)?);
+            match (t, f) {
+                (true, false) => a,
+                (false, true) => b,
+                _ => join(a, b)?,
+            }
+        }
+        Node::InRange(x, lo, hi) => {
+            let x = infer(x, scope)?.range("a range test")?;
+            let lo = infer(lo, scope)?.range("a range bound")?;
+            let hi = infer(hi, scope)?.range("a range bound")?;
+            // x in lo..hi  ==  lo <= x and x <= hi
+            let above = cmp_ints("<=", lo, x);
+            let below = cmp_ints("<=", x, hi);
+            Ty::Bool(above.0 && below.0, above.1 || below.1)
+        }
+        Node::InSet(x, set) => {
+            let x = infer(x, scope)?;
+            let (mut t, mut always) = (false, false);
+            for m in set {
+                let (mt, mf) = compare("==", &x, &infer(m, scope)?)?.cond()?;
+                t |= mt;
+                always |= mt && !mf;
+            }
+            Ty::Bool(t, !always)
+        }
+        Node::Table(name, rows, i) => {
+            infer(i, scope)?.range("a table index")?;
+            entries(name, rows.iter())?
+        }
+        Node::Pick(name, rows) => entries(name, rows.iter().map(|(_, v)| v))?,
+    })
+}
+
+/// The type of a table's entries: the range of its numbers, or a string.
+fn entries<'a>(name: &str, rows: impl Iterator<Item = &'a Value>) -> Result<Ty, String> {
+    let (mut ints, mut strs) = (Vec::new(), 0);
+    for v in rows {
+        match v {
+            Value::Str(_) => strs += 1,
+            v => ints.push(v.as_int()),
+        }
+    }
+    match (ints.is_empty(), strs) {
+        (true, 0) => Ok(Ty::Int(0, 0)),
+        (true, _) => Ok(Ty::str()),
+        (false, 0) => Ok(Ty::Int(*ints.iter().min().unwrap(), *ints.iter().max().unwrap())),
+        _ => Err(format!("table {name:?} mixes strings and numbers")),
+    }
+}
+
+fn span(xs: &[i64]) -> (i64, i64) {
+    (*xs.iter().min().unwrap(), *xs.iter().max().unwrap())
+}
+
+fn compare(op: &str, a: &Ty, b: &Ty) -> Result<Ty, String> {
+    match (a, b) {
+        (Ty::Str(x), Ty::Str(y)) => {
+            if !matches!(op, "==" | "!=") {
+                return Err(format!("`{op}` on strings"));
+            }
+            let eq = match (x, y) {
+                (Some(x), Some(y)) => Some(x == y),
+                _ => None,
+            };
+            let (t, f) = match eq {
+                Some(e) => (e, !e),
+                None => (true, true),
+            };
+            Ok(if op == "==" { Ty::Bool(t, f) } else { Ty::Bool(f, t) })
+        }
+        (Ty::Str(_), _) | (_, Ty::Str(_)) => Err("compares a string with a number".into()),
+        _ => {
+            let (t, f) = cmp_ints(op, a.range("")?, b.range("")?);
+            Ok(Ty::Bool(t, f))
+        }
+    }
+}
+
+/// Whether `a op b` can be true and can be false, for `a`, `b` in those ranges.
+fn cmp_ints(op: &str, a: (i64, i64), b: (i64, i64)) -> (bool, bool) {
+    match op {
+        "<" => (a.0 < b.1, a.1 >= b.0),
+        "<=" => (a.0 <= b.1, a.1 > b.0),
+        ">" => cmp_ints("<", b, a),
+        ">=" => cmp_ints("<=", b, a),
+        "==" => (
+            a.0 <= b.1 && b.0 <= a.1,
+            !(a.0 == a.1 && b.0 == b.1 && a.0 == b.0),
+        ),
+        _ => {
+            let (t, f) = cmp_ints("==", a, b);
+            (f, t)
+        }
+    }
+}
+
+fn join(a: Ty, b: Ty) -> Result<Ty, String> {
+    Ok(match (a, b) {
+        (Ty::Bool(at, af), Ty::Bool(bt, bf)) => Ty::Bool(at || bt, af || bf),
+        (Ty::Str(x), Ty::Str(y)) => Ty::Str(if x == y { x } else { None }),
+        (Ty::Str(_), _) | (_, Ty::Str(_)) => {
+            return Err("if() branches mix a string and a number".into())
+        }
+        (a, b) => {
+            let (a, b) = (a.range("")?, b.range("")?);
+            Ty::Int(a.0.min(b.0), a.1.max(b.1))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::expr::compile;
+
+    fn scope() -> Scope {
+        Scope::new()
+            .with("atmo", Ty::Int(0, 15))
+            .with("hydro", Ty::Int(0, 10))
+            .with("port", Ty::str())
+            .with("naval", Ty::FLAG)
+            .pending(&["tech"])
+    }
+
+    fn cond(src: &str) -> Result<(), String> {
+        check(&compile(src).unwrap(), &scope(), Want::Cond)
+    }
+
+    #[test]
+    fn accepts_well_typed_rules() {
+        for ok in [
+            "atmo in 4..9 and hydro>=4",
+            "port in ['A','B'] or naval==1",
+            "if(port=='A', atmo, 0) > 3",
+            "max(atmo, hydro) >= 12",
+            "not (hydro in [0,10])",
+        ] {
+            assert_eq!(cond(ok), Ok(()), "{ok}");
+        }
+        let wtn = compile("(atmo + 1) / 2 + (port=='A') * 2 + 1d6").unwrap();
+        assert_eq!(check(&wtn, &scope(), Want::Number), Ok(()));
+    }
+
+    #[test]
+    fn reports_names_types_and_constant_conditions() {
+        assert!(cond("hydor>=4").unwrap_err().contains("undefined name `hydor`"));
+        assert!(cond("tech>=8").unwrap_err().contains("not known yet"));
+        assert!(cond("port==1").unwrap_err().contains("string with a number"));
+        assert!(cond("port>='B'").unwrap_err().contains("on strings"));
+        assert!(cond("naval and atmo==2").unwrap_err().contains("number used as a condition"));
+        assert!(cond("if(naval==1, 'A', 2)==2").unwrap_err().contains("mix"));
+        let num = check(&compile("port + 1").unwrap(), &scope(), Want::Number);
+        assert!(num.unwrap_err().contains("arithmetic on a string"));
+
+        assert_eq!(cond("hydro<=10").unwrap_err(), "condition is always true");
+        assert_eq!(cond("atmo>=2 and atmo>20").unwrap_err(), "condition is always false");
+        assert_eq!(cond("hydro in [11,12]").unwrap_err(), "condition is always false");
+        assert_eq!(cond("2d6 in 2..12").unwrap_err(), "condition is always true");
+        assert_eq!(cond("'A'=='A'").unwrap_err(), "condition is always true");
+    }
+}
@@ -1,13 +1,18 @@
 //! A sandboxed expression evaluator for ruleset rules — the Rust port of the Ruby
 //! Astromapper::Rules::Expr (and the Go pkg/rules expr). Supports integer & string
 //! literals, named variables, dice (`2d6`, `flux`), arithmetic (+ - * /), comparisons
-//! (== != < <= > >=), and booleans (and / or / not), with parentheses.
+//! (== != < <= > >=), and booleans (and / or / not), with parentheses. Also the
+//! built-in functions `min`, `max`, `clamp(x, lo, hi)` and `abs`, the conditional
+//! `if(cond, a, b)`, inclusive range tests (`atmo in 2..9`) and set membership
+//! (`atmo in [0,1,2,4,7,9]`, `port in ['A','B']`). A ruleset links in its `defs:`
+//! (named sub-expressions) and `tables:` (`table('gravity', size)` indexes one,
+//! `pick('name')` draws a weighted entry) once compiled; see `Node::link`.
 //!
 //! SECURITY: this is a hand-written tokenizer + recursive-descent parser. It never
 //! evaluates arbitrary code — only arithmetic, comparisons, dice, and lookups in a
 //! caller-supplied context are possible. A `rules/*.yml` file is data, not a program.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::rng;
 
@@ -23,6 +28,7 @@ impl Value {
     pub fn from_int<T: Into<i64>>(n: T) -> Value {
         Value::Int(n.into())
     }
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Value {
         Value::Str(s.to_string())
     }
@@ -55,8 +61,21 @@ pub enum Node {
     Or(Box<Node>, Box<Node>),
     Cmp(String, Box<Node>, Box<Node>),
     Arith(char, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+    If(Box<Node>, Box<Node>, Box<Node>),
+    InRange(Box<Node>, Box<Node>, Box<Node>),
+    InSet(Box<Node>, Vec<Node>),
+    Table(String, Vec<Value>, Box<Node>), // name, entries (filled by `link`), index
+    Pick(String, Vec<(i64, Value)>),      // name, weighted entries (filled by `link`)
 }
 
+/// A ruleset table: entries with their `pick` weights.
+pub type Table = Vec<(i64, Value)>;
+
+/// Built-in functions and their argument counts.
+const FUNCTIONS: [(&str, usize); 6] =
+    [("min", 2), ("max", 2), ("clamp", 3), ("abs", 1), ("table", 2), ("pick", 1)];
+
 impl Node {
     /// Evaluate against a context. Dice/flux draw from the thread-local RNG.
     pub fn eval(&self, ctx: &Context) -> Value {
@@ -91,13 +110,126 @@ impl Node {
                     }
                 })
             }
+            Node::Call(f, args) => {
+                let a: Vec<i64> = args.iter().map(|x| x.eval(ctx).as_int()).collect();
+                Value::Int(match f.as_str() {
+                    "min" => a[0].min(a[1]),
+                    "max" => a[0].max(a[1]),
+                    "clamp" => a[0].max(a[1]).min(a[2]),
+                    _ => a[0].abs(),
+                })
+            }
+            Node::If(c, a, b) => {
+                if c.eval(ctx).truthy() {
+                    a.eval(ctx)
+                } else {
+                    b.eval(ctx)
+                }
+            }
+            Node::InRange(x, lo, hi) => {
+                let n = x.eval(ctx).as_int();
+                Value::Bool(lo.eval(ctx).as_int() <= n && n <= hi.eval(ctx).as_int())
+            }
+            Node::InSet(x, set) => {
+                let v = x.eval(ctx);
+                Value::Bool(set.iter().any(|m| values_equal(&v, &m.eval(ctx))))
+            }
+            Node::Table(_, rows, i) => {
+                if rows.is_empty() {
+                    return Value::Int(0);
+                }
+                let i = i.eval(ctx).as_int().clamp(0, rows.len() as i64 - 1);
+                rows[i as usize].clone()
+            }
+            Node::Pick(_, rows) => {
+                let total: i64 = rows.iter().map(|(w, _)| (*w).max(0)).sum();
+                if total <= 0 {
+                    return Value::Int(0);
+                }
+                let mut r = rng::roll_range(total as usize).unwrap_or(0) as i64;
+                for (w, v) in rows {
+                    if r < (*w).max(0) {
+                        return v.clone();
+                    }
+                    r -= (*w).max(0);
+                }
+                Value::Int(0)
+            }
         }
     }
 
+    /// Resolve a ruleset's names: a variable naming one of `defs` becomes that
+    /// definition (itself linked), and each `table`/`pick` takes its table's entries.
+    /// Errors on an unknown table or a definition that refers back to itself.
+    pub fn link(self, defs: &HashMap<String, Node>, tables: &HashMap<String, Table>) -> Result<Node, String> {
+        self.link_in(defs, tables, &mut HashSet::new())
+    }
+
+    fn link_in(
+        self,
+        defs: &HashMap<String, Node>,
+        tables: &HashMap<String, Table>,
+        active: &mut HashSet<String>,
+    ) -> Result<Node, String> {
+        let mut go = |n: Node| n.link_in(defs, tables, active);
+        let bx = |n: Result<Node, String>| n.map(Box::new);
+        let table = |name: &str| {
+            tables.get(name).cloned().ok_or_else(|| format!("unknown table {name:?}"))
+        };
+        Ok(match self {
+            Node::Var(k) if defs.contains_key(&k) => {
+                if !active.insert(k.clone()) {
+                    return Err(format!("definition {k:?} refers to itself"));
+                }
+                let node = defs[&k].clone().link_in(defs, tables, active)?;
+                active.remove(&k);
+                node
+            }
+            Node::Neg(x) => Node::Neg(bx(go(*x))?),
+            Node::Not(x) => Node::Not(bx(go(*x))?),
+            Node::And(l, r) => Node::And(bx(go(*l))?, bx(go(*r))?),
+            Node::Or(l, r) => Node::Or(bx(go(*l))?, bx(go(*r))?),
+            Node::Cmp(op, l, r) => Node::Cmp(op, bx(go(*l))?, bx(go(*r))?),
+            Node::Arith(op, l, r) => Node::Arith(op, bx(go(*l))?, bx(go(*r))?),
+            Node::Call(f, args) => Node::Call(f, args.into_iter().map(go).collect::<Result<_, _>>()?),
+            Node::If(c, a, b) => Node::If(bx(go(*c))?, bx(go(*a))?, bx(go(*b))?),
+            Node::InRange(x, lo, hi) => Node::InRange(bx(go(*x))?, bx(go(*lo))?, bx(go(*hi))?),
+            Node::InSet(x, set) => {
+                Node::InSet(bx(go(*x))?, set.into_iter().map(go).collect::<Result<_, _>>()?)
+            }
+            Node::Table(name, _, i) => {
+                let rows = table(&name)?.into_iter().map(|(_, v)| v).collect();
+                Node::Table(name, rows, bx(go(*i))?)
+            }
+            Node::Pick(name, _) => {
+                let rows = table(&name)?;
+                Node::Pick(name, rows)
+            }
+            leaf => leaf,
+        })
+    }
+
     /// Convenience: evaluate and return whether the result is boolean-true.
     pub fn is_true(&self, ctx: &Context) -> bool {
         matches!(self.eval(ctx), Value::Bool(true))
     }
+
+    /// Whether every variable the expression reads is set in `ctx`.
+    pub fn bound_in(&self, ctx: &Context) -> bool {
+        match self {
+            Node::Var(k) => ctx.contains_key(k),
+            Node::Neg(x) | Node::Not(x) | Node::Table(_, _, x) => x.bound_in(ctx),
+            Node::And(l, r) | Node::Or(l, r) | Node::Cmp(_, l, r) | Node::Arith(_, l, r) => {
+                l.bound_in(ctx) && r.bound_in(ctx)
+            }
+            Node::If(a, b, c) | Node::InRange(a, b, c) => {
+                a.bound_in(ctx) && b.bound_in(ctx) && c.bound_in(ctx)
+            }
+            Node::Call(_, args) => args.iter().all(|a| a.bound_in(ctx)),
+            Node::InSet(x, set) => x.bound_in(ctx) && set.iter().all(|a| a.bound_in(ctx)),
+            _ => true,
+        }
+    }
 }
 
 fn compare(op: &str, a: &Value, b: &Value) -> bool {
@@ -191,12 +323,12 @@ fn tokenize(src: &str) -> Result<Vec<Token>, String> {
         }
         // two-char operators
         let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
-        if two == "==" || two == "!=" || two == "<=" || two == ">=" {
+        if two == "==" || two == "!=" || two == "<=" || two == ">=" || two == ".." {
             toks.push(Token::Op(two));
             i += 2;
             continue;
         }
-        if "<>()+-*/".contains(c) {
+        if "<>()+-*/[],".contains(c) {
             toks.push(Token::Op(c.to_string()));
             i += 1;
             continue;
@@ -207,7 +339,7 @@ fn tokenize(src: &str) -> Result<Vec<Token>, String> {
                 i += 1;
             }
             let w: String = chars[start..i].iter().collect();
-            if matches!(w.as_str(), "and" | "or" | "not" | "flux") {
+            if matches!(w.as_str(), "and" | "or" | "not" | "flux" | "in" | "if") {
                 toks.push(Token::Kw(w));
             } else {
                 toks.push(Token::Var(w));
@@ -221,7 +353,7 @@ fn tokenize(src: &str) -> Result<Vec<Token>, String> {
 }
 
 // ---- parser (recursive descent) ----------------------------------------
-// or > and > not > comparison > additive > multiplicative > unary > primary
+// or > and > not > comparison / in > additive > multiplicative > unary > primary
 
 struct Parser {
     tokens: Vec<Token>,
@@ -281,6 +413,18 @@ impl Parser {
 
     fn parse_cmp(&mut self) -> Result<Node, String> {
         let node = self.parse_add()?;
+        if self.accept_kw("in") {
+            if self.accept_op("[") {
+                let set = self.parse_list("]")?;
+                return Ok(Node::InSet(Box::new(node), set));
+            }
+            let lo = self.parse_add()?;
+            if !self.accept_op("..") {
+                return Err("expected '..' or '[' after 'in'".to_string());
+            }
+            let hi = self.parse_add()?;
+            return Ok(Node::InRange(Box::new(node), Box::new(lo), Box::new(hi)));
+        }
         for op in ["==", "!=", "<=", ">=", "<", ">"] {
             if self.accept_op(op) {
                 let rhs = self.parse_add()?;
@@ -290,6 +434,23 @@ impl Parser {
         Ok(node)
     }
 
+    /// Comma-separated expressions up to the closing `close` (already past the opener).
+    fn parse_list(&mut self, close: &str) -> Result<Vec<Node>, String> {
+        let mut items = Vec::new();
+        if self.accept_op(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.parse_or()?);
+            if self.accept_op(close) {
+                return Ok(items);
+            }
+            if !self.accept_op(",") {
+                return Err(format!("expected ',' or '{close}'"));
+            }
+        }
+    }
+
     fn parse_add(&mut self) -> Result<Node, String> {
         let mut node = self.parse_mul()?;
         loop {
@@ -335,6 +496,36 @@ impl Parser {
             Token::Str(s) => Ok(Node::Str(s)),
             Token::Dice(n, sides) => Ok(Node::Dice(n, sides)),
             Token::Kw(k) if k == "flux" => Ok(Node::Flux),
+            Token::Kw(k) if k == "if" => {
+                if !self.accept_op("(") {
+                    return Err("expected '(' after 'if'".to_string());
+                }
+                let mut args = self.parse_list(")")?;
+                if args.len() != 3 {
+                    return Err(format!("if takes 3 arguments, got {}", args.len()));
+                }
+                let (b, a, c) = (args.pop().unwrap(), args.pop().unwrap(), args.pop().unwrap());
+                Ok(Node::If(Box::new(c), Box::new(a), Box::new(b)))
+            }
+            Token::Var(v) if self.accept_op("(") => {
+                let Some(&(_, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == v) else {
+                    return Err(format!("unknown function {v:?}"));
+                };
+                let mut args = self.parse_list(")")?;
+                if args.len() != arity {
+                    return Err(format!("{v} takes {arity} argument(s), got {}", args.len()));
+                }
+                if v == "table" || v == "pick" {
+                    let Node::Str(name) = args.remove(0) else {
+                        return Err(format!("{v} needs a table name in quotes, e.g. {v}('gravity', ...)"));
+                    };
+                    return Ok(match args.pop() {
+                        Some(i) => Node::Table(name, Vec::new(), Box::new(i)),
+                        None => Node::Pick(name, Vec::new()),
+                    });
+                }
+                Ok(Node::Call(v, args))
+            }
             Token::Var(v) => Ok(Node::Var(v)),
             Token::Op(ref o) if o == "(" => {
                 let node = self.parse_or()?;
@@ -401,10 +592,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn functions_conditionals_and_membership() {
+        assert_eq!(ev("min(3, 9) + max(3, 9)").as_int(), 12);
+        assert_eq!(ev("clamp(14, 0, 10)").as_int(), 10);
+        assert_eq!(ev("clamp(-2, 0, 10)").as_int(), 0);
+        assert_eq!(ev("abs(2-7)").as_int(), 5);
+        assert_eq!(ev("if(2>1, 'yes', 'no')"), Value::Str("yes".into()));
+        assert_eq!(ev("if(0>1, 4, 2*3)").as_int(), 6);
+
+        let mut ctx = Context::new();
+        ctx.insert("atmo".into(), Value::Int(9));
+        ctx.insert("port".into(), Value::Str("B".into()));
+        let t = |src: &str| compile(src).unwrap().is_true(&ctx);
+        assert!(t("atmo in 2..9"));
+        assert!(!t("atmo in 2..8"));
+        assert!(t("atmo in [0,1,2,4,7,9] and port in ['A','B']"));
+        assert!(!t("atmo in []"));
+        assert!(t("not atmo in [3] and atmo+1 in 5..max(atmo, 12)"));
+    }
+
+    #[test]
+    fn linked_defs_and_tables() {
+        rng::init_rng("expr-tables");
+        let defs: HashMap<String, Node> = [
+            ("garden", "atmo in 4..9 and wet"),
+            ("wet", "hydro>=4"),
+            ("loop", "loop or 1==1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), compile(v).unwrap()))
+        .collect();
+        let tables: HashMap<String, Table> = HashMap::from([
+            ("gravity".to_string(), vec![(1, Value::Int(0)), (1, Value::Int(5)), (1, Value::Int(15))]),
+            ("port".to_string(), vec![(3, Value::from_str("A")), (0, Value::from_str("B")), (1, Value::from_str("C"))]),
+        ]);
+        let link = |src: &str| compile(src).unwrap().link(&defs, &tables);
+        let mut ctx = Context::new();
+        ctx.insert("atmo".into(), Value::Int(6));
+        ctx.insert("hydro".into(), Value::Int(5));
+        ctx.insert("size".into(), Value::Int(1));
+        assert!(link("garden and size==1").unwrap().is_true(&ctx));
+        assert_eq!(link("table('gravity', size)").unwrap().eval(&ctx), Value::Int(5));
+        assert_eq!(link("table('gravity', size + 9)").unwrap().eval(&ctx), Value::Int(15), "clamped");
+        let pick = link("pick('port')").unwrap();
+        let picks: Vec<Value> = (0..200).map(|_| pick.eval(&ctx)).collect();
+        assert!(!picks.contains(&Value::from_str("B")), "zero weight is never drawn");
+        assert!(picks.iter().filter(|p| **p == Value::from_str("A")).count() > 100);
+
+        assert!(link("table('nonesuch', 1)").unwrap_err().contains("unknown table"));
+        assert!(link("loop").unwrap_err().contains("refers to itself"));
+        assert!(compile("table(size, 1)").is_err());
+    }
+
     #[test]
     fn rejects_bad_input() {
         assert!(compile("1 +").is_err());
         assert!(compile("((1)").is_err());
         assert!(compile("2 @ 3").is_err());
+        assert!(compile("floor(2)").is_err());
+        assert!(compile("min(1)").is_err());
+        assert!(compile("if(1, 2)").is_err());
+        assert!(compile("atmo in 2").is_err());
+        assert!(compile("atmo in [1,2").is_err());
     }
 }
@@ -2,9 +2,15 @@
 //! ruleset loader (`ruleset`), mirroring the Ruby and Go implementations so all
 //! three share the same `rules/<name>.yml` definitions.
 
+pub mod check;
 pub mod expr;
+pub mod modules;
 pub mod ruleset;
 pub mod runtime;
+pub mod script;
 
 pub use expr::{Context, Value};
-pub use ruleset::Ruleset;
+pub use modules::{
+    ClimateModule, ExtensionsModule, Module, NativeModule, NoModule, OrbitModule, StarModule, T5Module,
+};
+pub use ruleset::{Ruleset, TradeGood};
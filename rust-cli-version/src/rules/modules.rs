@@ -0,0 +1,93 @@
+//! The algorithmic half of a ruleset: the generation steps too procedural for a
+//! table. A ruleset's `modules:` block names one implementation per slot; the
+//! built-ins are `t5` ([`T5Module`]) and, for the optional world passes, `none`
+//! ([`NoModule`]). A program built on the library adds its own with
+//! `runtime::register_module` before loading the ruleset that names them, and with
+//! the `scripting` feature a ruleset can name a Rhai script (`script:rules/x.rhai`,
+//! see `script.rs`).
+
+use std::rc::Rc;
+
+use crate::error::Result;
+use crate::models::world::{Temperature, World};
+use crate::models::Star;
+
+/// The `modules:` slots, in generation order.
+pub const SLOTS: [&str; 5] = ["stars", "orbits", "climate", "extensions", "native"];
+
+/// Rolls a system's primary star (with any companions, but no orbits yet).
+pub trait StarModule {
+    fn primary(&self, genre: &str) -> Result<Star>;
+}
+
+/// Fills a star's orbits, placing the mainworld.
+pub trait OrbitModule {
+    fn populate_orbits(&self, star: &mut Star) -> Result<()>;
+}
+
+/// The mainworld's temperature band, rolled once size, atmosphere and
+/// hydrographics are known.
+pub trait ClimateModule {
+    fn climate(&self, world: &World, orbit_number: u8) -> Result<Temperature>;
+}
+
+/// The post-pass over a finished mainworld once its system's gas giants and belts
+/// are counted (T5's Ix/Ex/Cx and Resource Units).
+pub trait ExtensionsModule {
+    fn build_extensions(&self, world: &mut World, gas_giants: i64, belts: i64) -> Result<()>;
+}
+
+/// Who lives on the mainworld (Settled, Colony, Native, ...); empty for nobody.
+pub trait NativeModule {
+    fn native_status(&self, world: &World) -> Result<String>;
+}
+
+/// The built-in Traveller 5 implementation of every slot.
+pub struct T5Module;
+
+/// The built-in `none`: leaves climate temperate, skips the extensions, and gives
+/// no native status. Stars and orbits cannot be switched off.
+pub struct NoModule;
+
+/// One registered implementation, tagged with its slot.
+#[derive(Clone)]
+pub enum Module {
+    Stars(Rc<dyn StarModule>),
+    Orbits(Rc<dyn OrbitModule>),
+    Climate(Rc<dyn ClimateModule>),
+    Extensions(Rc<dyn ExtensionsModule>),
+    Native(Rc<dyn NativeModule>),
+}
+
+impl Module {
+    /// The `modules:` key this implementation fills.
+    pub fn slot(&self) -> &'static str {
+        match self {
+            Module::Stars(_) => "stars",
+            Module::Orbits(_) => "orbits",
+            Module::Climate(_) => "climate",
+            Module::Extensions(_) => "extensions",
+            Module::Native(_) => "native",
+        }
+    }
+
+    /// A built-in by slot and name.
+    pub fn builtin(slot: &str, name: &str) -> Option<Module> {
+        Some(match (slot, name) {
+            ("stars", "t5") => Module::Stars(Rc::new(T5Module)),
+            ("orbits", "t5") => Module::Orbits(Rc::new(T5Module)),
+            ("climate", "t5") => Module::Climate(Rc::new(T5Module)),
+            ("climate", "none") => Module::Climate(Rc::new(NoModule)),
+            ("extensions", "t5") => Module::Extensions(Rc::new(T5Module)),
+            ("extensions", "none") => Module::Extensions(Rc::new(NoModule)),
+            ("native", "t5") => Module::Native(Rc::new(T5Module)),
+            ("native", "none") => Module::Native(Rc::new(NoModule)),
+            _ => return None,
+        })
+    }
+
+    /// The built-in names for a slot.
+    pub fn builtin_names(slot: &str) -> Vec<&'static str> {
+        ["none", "t5"].into_iter().filter(|n| Module::builtin(slot, n).is_some()).collect()
+    }
+}
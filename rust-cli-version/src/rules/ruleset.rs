@@ -1,28 +1,83 @@
 //! The data-driven ruleset loader — Rust port of Ruby Astromapper::Rules::Ruleset
 //! and Go pkg/rules. Loads `rules/<name>.yml` (embedded built-ins, with project-dir
-//! override), resolves `extends:` inheritance, and exposes trade codes, UWP step
-//! formulas, and the starport/tech/base tables. serde_yaml::Mapping preserves key
-//! order, so trade-code output order follows the YAML.
+//! override), resolves `extends:`/`include:` (noting which file set each value),
+//! and exposes trade codes, UWP step formulas, and the starport/tech/base tables.
+//! serde_yaml::Mapping preserves key order, so trade-code output order follows the
+//! YAML.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 
 use serde_yaml::Value as Yaml;
 
-use super::expr::{self, Context, Node, Value};
+use super::check::{self, Scope, Ty, Want};
+use super::expr::{self, Context, Node, Table, Value};
+use super::{modules, runtime, script};
+use crate::models::world::Base;
+use crate::models::{StarSize, StarType};
+use crate::rng;
 
 pub struct Ruleset {
     name: String,
+    root: String, // project directory it was loaded from; scripts resolve against it
     data: Yaml,
     trade: Vec<(String, Node)>, // compiled trade conditions, in YAML order
-    uwp_cache: HashMap<String, Node>,
+    goods: Vec<TradeGood>,
+    exprs: HashMap<String, Node>, // other compiled formulas, keyed by YAML path (uwp/size/roll)
+    tables: HashMap<String, Table>,
+    layout: Vec<String>, // `uwp_layout` entries; digits are compiled as uwp_layout/<i>
+    origins: Origins,
+}
+
+/// One row of the speculative-trade table. DMs are keyed by trade code; `available`
+/// lists the source codes that stock it (`All` = everywhere).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TradeGood {
+    pub name: String,
+    pub price: i64, // base Cr per ton
+    pub available: Vec<String>,
+    pub purchase: Vec<(String, i64)>,
+    pub sale: Vec<(String, i64)>,
+}
+
+impl TradeGood {
+    fn parse(v: &Yaml) -> Option<TradeGood> {
+        let dms = |key: &str| -> Vec<(String, i64)> {
+            v.get(key)
+                .and_then(|m| m.as_mapping())
+                .map(|m| {
+                    m.iter()
+                        .filter_map(|(k, d)| Some((k.as_str()?.to_string(), d.as_i64()?)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Some(TradeGood {
+            name: v.get("name")?.as_str()?.to_string(),
+            price: v.get("price")?.as_i64()?,
+            available: v
+                .get("available")
+                .and_then(|a| a.as_sequence())
+                .map(|a| a.iter().filter_map(|x| x.as_str()).map(String::from).collect())
+                .unwrap_or_else(|| vec!["All".to_string()]),
+            purchase: dms("purchase"),
+            sale: dms("sale"),
+        })
+    }
+
+    /// Whether a world with these trade codes stocks the good.
+    pub fn available_at(&self, codes: &[String]) -> bool {
+        self.available.iter().any(|a| a == "All" || codes.contains(a))
+    }
 }
 
 fn builtin(name: &str) -> Option<&'static str> {
     match name {
         "t5" => Some(include_str!("builtin/t5.yml")),
         "cepheus" => Some(include_str!("builtin/cepheus.yml")),
+        "mgt2" => Some(include_str!("builtin/mgt2.yml")),
+        "classic" => Some(include_str!("builtin/classic.yml")),
         _ => None,
     }
 }
@@ -31,69 +86,284 @@ impl Ruleset {
     /// Load a ruleset by name: project `rules/<name>.yml` first, then the built-in,
     /// applying `extends:` before validating.
     pub fn load(name: &str, project_root: &str) -> Result<Ruleset, String> {
-        let data = load_merged(name, project_root, &mut Vec::new())?;
-        let rs = Ruleset::build(name.to_string(), data)?;
+        let (data, origins) = load_merged(name, project_root, &mut Vec::new())?;
+        let mut rs = Ruleset::build(name.to_string(), data)?;
+        rs.root = project_root.to_string();
+        rs.origins = origins;
         rs.validate()?;
         Ok(rs)
     }
 
     fn build(name: String, data: Yaml) -> Result<Ruleset, String> {
+        let tables = parse_tables(&data).map_err(|e| format!("ruleset {name:?}: {e}"))?;
+        let mut defs = HashMap::new();
+        if let Some(d) = data.get("defs").and_then(|v| v.as_mapping()) {
+            for (k, v) in d.iter() {
+                let (Some(key), Some(src)) = (k.as_str(), expr_source(v)) else { continue };
+                let code = data.get("trade_codes").and_then(|t| t.get(key)).is_some();
+                if WORLD_VARS.contains(&key) || code {
+                    return Err(format!("ruleset {name:?}: defs.{key}: shadows the variable `{key}`"));
+                }
+                let node = expr::compile(&src)
+                    .map_err(|e| format!("ruleset {name:?}: defs.{key}: {e}"))?;
+                defs.insert(key.to_string(), node);
+            }
+        }
+        // Every formula is compiled through this, so `defs` and `tables` work anywhere.
+        let compile = |src: &str| expr::compile(src).and_then(|n| n.link(&defs, &tables));
         let mut trade = Vec::new();
         if let Some(tc) = data.get("trade_codes").and_then(|v| v.as_mapping()) {
             for (k, v) in tc.iter() {
                 if let (Some(code), Some(cond)) = (k.as_str(), v.as_str()) {
-                    let node = expr::compile(cond)
+                    let node = compile(cond)
                         .map_err(|e| format!("ruleset {name:?}: trade code {code}: {e}"))?;
                     trade.push((code.to_string(), node));
                 }
             }
         }
-        let mut uwp_cache = HashMap::new();
+        // Each formula is compiled and cached under its YAML path ('/'-separated);
+        // errors name the path dotted, as `validate` does.
+        let mut exprs = HashMap::new();
+        let compile_at = |exprs: &mut HashMap<String, Node>, path: String, v: Option<&Yaml>| {
+            if let Some(src) = v.and_then(expr_source) {
+                let node = compile(&src)
+                    .map_err(|e| format!("ruleset {name:?}: {}: {e}", path.replace('/', ".")))?;
+                exprs.insert(path, node);
+            }
+            Ok::<(), String>(())
+        };
         if let Some(uwp) = data.get("uwp").and_then(|v| v.as_mapping()) {
             for (k, spec) in uwp.iter() {
-                let step = match k.as_str() {
-                    Some(s) => s,
-                    None => continue,
-                };
-                let mut cache = |key: String, src: &str| -> Result<(), String> {
-                    let node =
-                        expr::compile(src).map_err(|e| format!("ruleset {name:?}: {key}: {e}"))?;
-                    uwp_cache.insert(key, node);
-                    Ok(())
-                };
+                let Some(step) = k.as_str() else { continue };
                 for key in ["zero_when", "roll"] {
-                    if let Some(s) = spec.get(key).and_then(|x| x.as_str()) {
-                        cache(format!("uwp/{step}/{key}"), s)?;
-                    }
+                    compile_at(&mut exprs, format!("uwp/{step}/{key}"), spec.get(key))?;
                 }
-                if let Some(rr) = spec.get("reroll").and_then(|x| x.as_mapping()) {
+                if let Some(rr) = spec.get("reroll") {
                     for key in ["when", "with"] {
-                        if let Some(s) = rr.get(key).and_then(|x| x.as_str()) {
-                            cache(format!("uwp/{step}/reroll/{key}"), s)?;
-                        }
+                        compile_at(&mut exprs, format!("uwp/{step}/reroll/{key}"), rr.get(key))?;
                     }
                 }
                 if let Some(adj) = spec.get("adjust").and_then(|x| x.as_sequence()) {
                     for (i, a) in adj.iter().enumerate() {
-                        if let Some(s) = a.get("when").and_then(|x| x.as_str()) {
-                            cache(format!("uwp/{step}/adjust/{i}"), s)?;
+                        compile_at(&mut exprs, format!("uwp/{step}/adjust/{i}"), a.get("when"))?;
+                    }
+                }
+            }
+        }
+        if let Some(mil) = data.get("military").and_then(|v| v.as_mapping()) {
+            for (k, v) in mil.iter() {
+                let Some(key) = k.as_str() else { continue };
+                compile_at(&mut exprs, format!("military/{key}"), Some(v))?;
+            }
+        }
+        compile_at(&mut exprs, "xboat/hub".into(), data.get("xboat").and_then(|x| x.get("hub")))?;
+        if let Some(tr) = data.get("traffic").and_then(|v| v.as_mapping()) {
+            for (k, v) in tr.iter() {
+                let Some(key) = k.as_str() else { continue };
+                match v.as_mapping() {
+                    Some(lots) if key == "lots" => {
+                        for (c, f) in lots.iter() {
+                            let Some(cargo) = c.as_str() else { continue };
+                            compile_at(&mut exprs, format!("traffic/lots/{cargo}"), Some(f))?;
                         }
                     }
+                    Some(_) => {}
+                    None if v.is_string() => compile_at(&mut exprs, format!("traffic/{key}"), Some(v))?,
+                    None => {}
                 }
             }
         }
+        let mut layout = Vec::new();
+        match data.get("uwp_layout") {
+            Some(Yaml::Sequence(entries)) => {
+                for v in entries {
+                    layout.push(expr_source(v).ok_or_else(|| {
+                        format!("ruleset {name:?}: uwp_layout: entries are names, formulas or separators")
+                    })?);
+                }
+            }
+            Some(_) => return Err(format!("ruleset {name:?}: uwp_layout: want a list")),
+            None => layout.extend(DEFAULT_UWP_LAYOUT.map(String::from)),
+        }
+        for (i, entry) in layout.iter().enumerate() {
+            if !is_separator(entry) {
+                compile_at(&mut exprs, format!("uwp_layout/{i}"), Some(&Yaml::from(entry.as_str())))?;
+            }
+        }
+        if let Some(port) = data.get("starport") {
+            compile_at(&mut exprs, "starport/roll".into(), port.get("roll"))?;
+        }
+        if let Some(fac) = data
+            .get("starport")
+            .and_then(|s| s.get("facilities"))
+            .and_then(|f| f.as_mapping())
+        {
+            for (k, spec) in fac.iter() {
+                let Some(class) = k.as_str() else { continue };
+                for key in FACILITY_VALUES {
+                    compile_at(&mut exprs, format!("starport/facilities/{class}/{key}"), spec.get(key))?;
+                }
+                for key in FACILITY_TEXTS {
+                    let Some(rows) = spec.get(key).and_then(|r| r.as_sequence()) else {
+                        continue;
+                    };
+                    for (i, row) in rows.iter().enumerate() {
+                        let path = format!("starport/facilities/{class}/{key}/{i}");
+                        compile_at(&mut exprs, path, row.get("when"))?;
+                    }
+                }
+            }
+        }
+        if let Some(bases) = data.get("bases").and_then(|b| b.as_mapping()) {
+            for (k, spec) in bases.iter() {
+                let Some(kind) = k.as_str() else { continue };
+                compile_at(&mut exprs, format!("bases/{kind}/roll"), spec.get("roll"))?;
+                compile_at(&mut exprs, format!("bases/{kind}/when"), spec.get("when"))?;
+                if let Some(rows) = spec.get("dm").and_then(|d| d.as_sequence()) {
+                    for (i, row) in rows.iter().enumerate() {
+                        compile_at(&mut exprs, format!("bases/{kind}/dm/{i}/when"), row.get("when"))?;
+                        compile_at(&mut exprs, format!("bases/{kind}/dm/{i}/dm"), row.get("dm"))?;
+                    }
+                }
+            }
+        }
+        compile_at(&mut exprs, "trade/wtn".into(), data.get("trade").and_then(|t| t.get("wtn")))?;
+        let roll = data.get("speculation").and_then(|t| t.get("roll"));
+        compile_at(&mut exprs, "speculation/roll".into(), roll)?;
+        if let Some(cul) = data.get("culture") {
+            if let Some(rows) = cul.get("offworlders").and_then(|v| v.as_sequence()) {
+                for (i, row) in rows.iter().enumerate() {
+                    compile_at(&mut exprs, format!("culture/offworlders/{i}"), row.get("when"))?;
+                }
+            }
+            for kind in ["religions", "languages", "quirks"] {
+                let Some(spec) = cul.get(kind) else { continue };
+                compile_at(&mut exprs, format!("culture/{kind}/count"), spec.get("count"))?;
+                if let Some(rows) = spec.get("always").and_then(|v| v.as_sequence()) {
+                    for (i, row) in rows.iter().enumerate() {
+                        compile_at(&mut exprs, format!("culture/{kind}/always/{i}"), row.get("when"))?;
+                    }
+                }
+            }
+        }
+        if let Some(stars) = data.get("stars") {
+            for group in ["types", "classes"] {
+                let Some(tables) = stars.get(group).and_then(|t| t.as_mapping()) else { continue };
+                for (k, spec) in tables.iter() {
+                    let Some(table) = k.as_str() else { continue };
+                    compile_at(&mut exprs, format!("stars/{group}/{table}/roll"), spec.get("roll"))?;
+                    if let Some(rows) = spec.get("adjust").and_then(|a| a.as_sequence()) {
+                        for (i, row) in rows.iter().enumerate() {
+                            let path = format!("stars/{group}/{table}/adjust/{i}");
+                            compile_at(&mut exprs, path, row.get("when"))?;
+                        }
+                    }
+                }
+            }
+            compile_at(&mut exprs, "stars/subtype".into(), stars.get("subtype"))?;
+            if let Some(comp) = stars.get("companions") {
+                compile_at(&mut exprs, "stars/companions/count".into(), comp.get("count"))?;
+                compile_at(&mut exprs, "stars/companions/orbit".into(), comp.get("orbit"))?;
+            }
+        }
+        if let Some(genres) = data.get("genres").and_then(|g| g.as_mapping()) {
+            for (k, spec) in genres.iter() {
+                let Some(genre) = k.as_str() else { continue };
+                // A bare table name needs no formula.
+                let census = spec.get("census").filter(|c| !c.as_str().is_some_and(is_name));
+                compile_at(&mut exprs, format!("genres/{genre}/census"), census)?;
+                for key in ["pop_dm", "port_dm"] {
+                    compile_at(&mut exprs, format!("genres/{genre}/{key}"), spec.get(key))?;
+                }
+                for step in ["atmo", "hydro"] {
+                    let Some(rows) = spec.get(step).and_then(|r| r.as_sequence()) else { continue };
+                    for (i, row) in rows.iter().enumerate() {
+                        compile_at(&mut exprs, format!("genres/{genre}/{step}/{i}"), row.get("when"))?;
+                    }
+                }
+            }
+        }
+        if let Some(orbits) = data.get("orbits") {
+            compile_at(&mut exprs, "orbits/count".into(), orbits.get("count"))?;
+            compile_at(&mut exprs, "orbits/mainworld".into(), orbits.get("mainworld"))?;
+            for zone in ORBIT_ZONES {
+                let Some(spec) = orbits.get("zones").and_then(|z| z.get(zone)) else { continue };
+                compile_at(&mut exprs, format!("orbits/zones/{zone}/roll"), spec.get("roll"))?;
+                compile_at(&mut exprs, format!("orbits/zones/{zone}/giant"), spec.get("giant"))?;
+                for key in ["atmo", "hydro"] {
+                    let v = spec.get("hostile").and_then(|h| h.get(key));
+                    compile_at(&mut exprs, format!("orbits/zones/{zone}/hostile/{key}"), v)?;
+                }
+            }
+            if let Some(moons) = orbits.get("moons") {
+                for key in ["count", "size", "orbit"] {
+                    compile_at(&mut exprs, format!("orbits/moons/{key}"), moons.get(key))?;
+                }
+                if let Some(bands) = moons.get("bands").and_then(|b| b.as_sequence()) {
+                    for (i, band) in bands.iter().enumerate() {
+                        compile_at(&mut exprs, format!("orbits/moons/bands/{i}/when"), band.get("when"))?;
+                        compile_at(&mut exprs, format!("orbits/moons/bands/{i}/roll"), band.get("roll"))?;
+                    }
+                }
+            }
+        }
+        let mut goods = Vec::new();
+        if let Some(list) = data.get("trade_goods").and_then(|g| g.as_sequence()) {
+            let default_tons = Yaml::from("1d6 * 10");
+            for (i, g) in list.iter().enumerate() {
+                let good = TradeGood::parse(g)
+                    .ok_or_else(|| format!("ruleset {name:?}: trade_goods[{i}]: needs `name` and `price`"))?;
+                let tons = g.get("tons").unwrap_or(&default_tons);
+                compile_at(&mut exprs, format!("trade_goods/{i}/tons"), Some(tons))?;
+                goods.push(good);
+            }
+        }
         Ok(Ruleset {
             name,
+            root: String::new(),
             data,
             trade,
-            uwp_cache,
+            goods,
+            exprs,
+            tables,
+            layout,
+            origins: Origins::new(),
         })
     }
 
+    /// Entry `i` of a `tables:` array (clamped to its ends); None without the table.
+    pub fn table_entry(&self, table: &str, i: i64) -> Option<Value> {
+        let rows = self.tables.get(table).filter(|t| !t.is_empty())?;
+        Some(rows[i.clamp(0, rows.len() as i64 - 1) as usize].1.clone())
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Where each value at or below `path` (dotted, e.g. `starport` or
+    /// `uwp.size.roll`; empty for all) was set: the ruleset file that supplied it
+    /// after `extends:`/`include:` merging, or `overlay` for config-supplied values.
+    pub fn origins(&self, path: &str) -> Vec<(&str, &str)> {
+        self.origins
+            .iter()
+            .filter(|(p, _)| under(p, path))
+            .map(|(p, f)| (p.as_str(), f.as_str()))
+            .collect()
+    }
+
+    /// The merged value at a dotted path (as `origins` lists them).
+    pub fn value_at(&self, path: &str) -> Option<&Yaml> {
+        path.split('.').try_fold(&self.data, |v, key| {
+            v.as_mapping()?.iter().find(|(k, _)| origin_path("", k) == key).map(|(_, v)| v)
+        })
+    }
+
+    /// The project directory the ruleset was loaded from (empty for a bare built-in).
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
     /// Human-facing name (the YAML `name:` field), falling back to the file slug.
     pub fn title(&self) -> String {
         self.data
@@ -106,9 +376,12 @@ impl Ruleset {
 
     pub fn validate(&self) -> Result<(), String> {
         let mut errs = Vec::new();
-        if self.data.get("hex").and_then(|v| v.as_str()).map_or(true, |s| s.is_empty()) {
+        if self.data.get("hex").and_then(|v| v.as_str()).is_none_or(|s| s.is_empty()) {
             errs.push("missing `hex` alphabet".to_string());
         }
+        if self.layout.is_empty() {
+            errs.push("uwp_layout: empty".to_string());
+        }
         for step in ["size", "atmo", "hydro", "pop", "gov", "law"] {
             let spec = self.data.get("uwp").and_then(|u| u.get(step));
             match spec {
@@ -126,11 +399,44 @@ impl Ruleset {
         {
             errs.push("missing `starport.table`".to_string());
         }
-        for slot in ["extensions", "climate", "native"] {
-            if let Err(e) = self.module_for(slot) {
-                errs.push(e);
+        if let Some(port) = self.data.get("starport") {
+            if port.get("method").is_some() {
+                errs.push(
+                    "starport.method: no longer read — `starport.roll` indexes `table` \
+                     directly, so order the table best-first for a low-is-best roll"
+                        .to_string(),
+                );
+            }
+            let dms = port.get("pop_dm");
+            if dms.is_some_and(|d| !d.as_sequence().is_some_and(|a| a.iter().all(|v| v.as_i64().is_some()))) {
+                errs.push("starport.pop_dm: want a list of DMs indexed by population".to_string());
             }
         }
+        errs.extend(self.check_genres());
+        errs.extend(self.check_stars());
+        errs.extend(self.check_orbits());
+        for kind in self.base_kinds() {
+            if Base::from_kind(&kind, self.base_code(&kind)).is_none() {
+                errs.push(format!("bases.{kind}: custom base kind needs a `code:` letter"));
+            }
+        }
+        for slot in modules::SLOTS {
+            match self.module_for(slot) {
+                Err(e) => errs.push(e),
+                Ok(name) if name.starts_with("script:") => {
+                    if let Err(e) = script::load(slot, &name["script:".len()..], &self.root) {
+                        errs.push(e);
+                    }
+                }
+                Ok(name) => {
+                    let known = runtime::module_names(slot);
+                    if !known.contains(&name) {
+                        errs.push(format!("modules.{slot}: unknown module {name:?} (known: {})", known.join(", ")));
+                    }
+                }
+            }
+        }
+        errs.extend(self.check_exprs());
         if errs.is_empty() {
             Ok(())
         } else {
@@ -142,85 +448,1091 @@ impl Ruleset {
         }
     }
 
-    pub fn trade_codes(&self, ctx: &Context) -> Vec<String> {
-        self.trade
-            .iter()
-            .filter(|(_, node)| node.is_true(ctx))
-            .map(|(code, _)| code.clone())
-            .collect()
+    /// The `genres:` section: a `normal` genre, and a census for each that names a
+    /// `stars.types` table (or is a formula giving one).
+    fn check_genres(&self) -> Vec<String> {
+        let Some(genres) = self.data.get("genres").and_then(|g| g.as_mapping()) else {
+            return vec!["missing `genres`".to_string()];
+        };
+        let mut errs = Vec::new();
+        if !genres.contains_key("normal") {
+            errs.push("genres: needs a `normal` genre".to_string());
+        }
+        let types = self.data.get("stars").and_then(|s| s.get("types"));
+        for (k, spec) in genres.iter() {
+            let Some(genre) = k.as_str() else { continue };
+            let word = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+            if genre.is_empty() || !genre.chars().all(word) {
+                errs.push(format!("genres.{genre}: genre names are lowercase words (a-z, 0-9, -, _)"));
+            }
+            match spec.get("census") {
+                None => errs.push(format!("genres.{genre}: no `census`")),
+                Some(c) => {
+                    let t = c.as_str().filter(|t| is_name(t));
+                    if let Some(t) = t.filter(|t| types.and_then(|x| x.get(t)).is_none()) {
+                        errs.push(format!("genres.{genre}.census: no table `stars.types.{t}`"));
+                    }
+                }
+            }
+        }
+        errs
+    }
+
+    /// The `stars:` section: table entries that are spectral letters, luminosity
+    /// classes or other tables, and physical data for every spectral type a table
+    /// can produce.
+    fn check_stars(&self) -> Vec<String> {
+        let Some(stars) = self.data.get("stars") else {
+            return vec!["missing `stars`".to_string()];
+        };
+        let mut errs = Vec::new();
+        if stars.get("classes").and_then(|c| c.get("default")).is_none() {
+            errs.push("stars.classes: needs a `default` table".to_string());
+        }
+        for group in ["types", "classes"] {
+            let Some(tables) = stars.get(group).and_then(|t| t.as_mapping()) else {
+                errs.push(format!("missing `stars.{group}`"));
+                continue;
+            };
+            for (k, spec) in tables.iter() {
+                let Some(table) = k.as_str() else { continue };
+                if spec.get("roll").is_none() {
+                    errs.push(format!("stars.{group}.{table}: no `roll`"));
+                }
+                let rows = spec.get("table").and_then(|t| t.as_sequence());
+                if rows.is_none_or(|r| r.is_empty()) {
+                    errs.push(format!("stars.{group}.{table}: no `table`"));
+                }
+                let sets = spec
+                    .get("adjust")
+                    .and_then(|a| a.as_sequence())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|a| a.get("set"));
+                for entry in rows.into_iter().flatten().chain(sets) {
+                    let code = entry.as_str().unwrap_or("");
+                    if star_code(group, code) {
+                        if group == "types" && stars.get("physical").and_then(|p| p.get(code)).is_none() {
+                            errs.push(format!("stars.physical: no entry for type {code}"));
+                        }
+                    } else if tables.get(code).is_none() {
+                        let what = if group == "types" { "spectral type" } else { "luminosity class" };
+                        errs.push(format!("stars.{group}.{table}: {code:?} is neither a {what} nor a table"));
+                    }
+                }
+            }
+        }
+        errs.sort();
+        errs.dedup();
+        errs
+    }
+
+    /// The `orbits:` section: a count, the three zone tables (entries from
+    /// ORBIT_BODIES), and moon bands that each have a table.
+    fn check_orbits(&self) -> Vec<String> {
+        let Some(orbits) = self.data.get("orbits") else {
+            return vec!["missing `orbits`".to_string()];
+        };
+        let mut errs = Vec::new();
+        for key in ["count", "mainworld"] {
+            if orbits.get(key).is_none() {
+                errs.push(format!("orbits: no `{key}`"));
+            }
+        }
+        for zone in ORBIT_ZONES {
+            let Some(spec) = orbits.get("zones").and_then(|z| z.get(zone)) else {
+                errs.push(format!("orbits.zones.{zone}: missing"));
+                continue;
+            };
+            let rows = spec.get("table").and_then(|t| t.as_sequence()).filter(|r| !r.is_empty());
+            if spec.get("roll").is_none() || rows.is_none() {
+                errs.push(format!("orbits.zones.{zone}: needs `roll` and `table`"));
+            }
+            for row in rows.into_iter().flatten() {
+                let body = row.as_str().unwrap_or("");
+                if !ORBIT_BODIES.contains(&body) {
+                    errs.push(format!(
+                        "orbits.zones.{zone}: unknown body {body:?} (known: {})",
+                        ORBIT_BODIES.join(", ")
+                    ));
+                }
+                if body == "hostile" && spec.get("hostile").is_none() {
+                    errs.push(format!("orbits.zones.{zone}: `hostile` rows need a `hostile:` atmo/hydro"));
+                }
+            }
+        }
+        let bands = orbits.get("moons").and_then(|m| m.get("bands")).and_then(|b| b.as_sequence());
+        for (i, band) in bands.into_iter().flatten().enumerate() {
+            if band.get("table").and_then(|t| t.as_sequence()).is_none_or(|t| t.is_empty()) {
+                errs.push(format!("orbits.moons.bands[{i}]: no `table`"));
+            }
+        }
+        errs.sort();
+        errs.dedup();
+        errs
+    }
+
+    /// Type-check every compiled formula against the variables its step sees,
+    /// reporting each problem with the formula's YAML path.
+    fn check_exprs(&self) -> Vec<String> {
+        let mut errs = Vec::new();
+        let world = self.world_scope(None);
+        for (code, node) in &self.trade {
+            if let Err(e) = check::check(node, &world, Want::Cond) {
+                errs.push(format!("trade_codes.{code}: {e}"));
+            }
+        }
+        let mut paths: Vec<&String> = self.exprs.keys().collect();
+        paths.sort();
+        for path in paths {
+            let (scope, want) = self.expr_scope(path);
+            if let Err(e) = check::check(&self.exprs[path], &scope, want) {
+                errs.push(format!("{}: {e}", path.replace('/', ".")));
+            }
+        }
+        errs
+    }
+
+    /// The range a UWP digit can take: its step's `clamp`, else the hex alphabet.
+    fn digit_ty(&self, step: &str) -> Ty {
+        let clamp = self
+            .data
+            .get("uwp")
+            .and_then(|u| u.get(step))
+            .and_then(|s| s.get("clamp"))
+            .and_then(|c| c.as_sequence())
+            .filter(|c| c.len() == 2);
+        match clamp {
+            Some(c) => Ty::Int(c[0].as_i64().unwrap_or(0), c[1].as_i64().unwrap_or(i64::MAX)),
+            None => {
+                let hex = self.data.get("hex").and_then(|h| h.as_str()).map_or(34, |h| h.chars().count());
+                Ty::Int(0, hex.max(1) as i64 - 1)
+            }
+        }
+    }
+
+    /// The world as the generator fills it in (size, atmo, hydro, temp, pop, gov, law,
+    /// port, tech). With `upto`, only the variables set before that UWP step; the rest
+    /// are pending.
+    fn world_scope(&self, upto: Option<&str>) -> Scope {
+        let mut scope = Scope::new();
+        let mut pending = Vec::new();
+        let mut open = true;
+        for var in WORLD_VARS {
+            if upto == Some(var) {
+                open = false;
+            }
+            if !open {
+                pending.push(var);
+                continue;
+            }
+            let ty = match var {
+                "temp" | "port" => Ty::str(),
+                "tech" => Ty::Int(0, 15),
+                _ => self.digit_ty(var),
+            };
+            scope.set(var, ty);
+        }
+        scope.pending(&pending)
+    }
+
+    /// The finished UWP digits, `tech` and `port` (no climate), as the extensions-era
+    /// contexts (military, X-boat hubs) carry them.
+    fn uwp_scope(&self) -> Scope {
+        let mut scope = Scope::new().with("tech", Ty::Int(0, 15)).with("port", Ty::str());
+        for var in ["size", "atmo", "hydro", "pop", "gov", "law"] {
+            scope.set(var, self.digit_ty(var));
+        }
+        scope
+    }
+
+    /// The variables in scope for the formula at `path`, and how its result is used.
+    /// Mirrors the contexts the builders, trade and navigation code evaluate with.
+    fn expr_scope(&self, path: &str) -> (Scope, Want) {
+        let parts: Vec<&str> = path.split('/').collect();
+        let want = |cond: bool| if cond { Want::Cond } else { Want::Number };
+        match parts.as_slice() {
+            ["uwp", step, rest @ ..] => {
+                let mut scope = self.world_scope(Some(step));
+                // reroll/adjust see the step's own raw (unclamped) roll.
+                if rest.first().is_some_and(|r| *r != "zero_when" && *r != "roll") {
+                    scope.set(step, Ty::INT);
+                }
+                let cond = matches!(rest, ["zero_when"] | ["reroll", "when"] | ["adjust", _]);
+                (scope, want(cond))
+            }
+            ["bases", _, rest @ ..] => {
+                let mut scope = self.world_scope(None);
+                for (code, _) in &self.trade {
+                    scope.set(code, Ty::FLAG);
+                }
+                (scope, want(matches!(rest, ["when"] | ["dm", _, "when"])))
+            }
+            // Rolled before Population; `pop_dm` adds the population DMs afterwards.
+            ["starport", "roll"] => (self.world_scope(Some("pop")), Want::Number),
+            ["starport", "facilities", _, _, _row] => (self.world_scope(None), Want::Cond),
+            ["uwp_layout", _] => (self.world_scope(None), Want::Digit),
+            ["starport", ..] | ["trade", "wtn"] => (self.world_scope(None), Want::Number),
+            ["military", step] => {
+                let mut scope = self.uwp_scope().with("ix", Ty::INT).with("ru", Ty::INT);
+                scope.set("naval", Ty::FLAG);
+                let order = MILITARY_STEPS.iter().position(|s| s == step).unwrap_or(MILITARY_STEPS.len());
+                for prior in &MILITARY_STEPS[..order] {
+                    scope.set(prior, Ty::COUNT);
+                }
+                if order > 2 {
+                    scope.set("navy", Ty::COUNT);
+                    scope.set("army", Ty::COUNT);
+                } else {
+                    scope = scope.pending(&["navy", "army"]);
+                }
+                (scope.pending(&MILITARY_STEPS[order..]), Want::Number)
+            }
+            ["culture", rest @ ..] => {
+                let mut scope = Scope::new();
+                for var in ["pop", "gov", "law"] {
+                    scope.set(var, self.digit_ty(var));
+                }
+                scope.set("tech", Ty::Int(0, 15));
+                scope.set("port", Ty::str());
+                for var in ["homo", "acc", "strange", "sym"] {
+                    scope.set(var, Ty::INT);
+                }
+                (scope, want(!matches!(rest, [_, "count"])))
+            }
+            ["xboat", "hub"] => {
+                let mut scope = self.uwp_scope().with("ix", Ty::INT);
+                for var in ["naval", "scout", "way", "depot"] {
+                    scope.set(var, Ty::FLAG);
+                }
+                (scope, Want::Cond)
+            }
+            ["traffic", "roll"] | ["traffic", "distance"] => {
+                (Scope::new().with("jump", Ty::COUNT), Want::Number)
+            }
+            ["traffic", rest @ ..] => {
+                let mut scope = Scope::new()
+                    .with("pop", self.digit_ty("pop"))
+                    .with("tech", Ty::Int(0, 15))
+                    .with("port", Ty::str())
+                    .with("zone", Ty::str());
+                if rest == ["mail"] {
+                    scope.set("freight", Ty::INT);
+                }
+                (scope, Want::Number)
+            }
+            ["genres", _, "census"] => (Scope::new(), Want::Text),
+            ["genres", _, step @ ("atmo" | "hydro"), _] => {
+                // The realism pass runs after climate: size..hydro and temp are set,
+                // with `step` its adjusted value so far.
+                let mut scope = self.world_scope(Some("pop"));
+                scope.set(step, self.digit_ty(step));
+                (scope, Want::Cond)
+            }
+            ["genres", _, dm] => {
+                let mut scope = self.world_scope(Some("pop"));
+                if *dm == "port_dm" {
+                    scope.set("pop", Ty::INT);
+                }
+                (scope, Want::Number)
+            }
+            ["stars", "types", _, "roll"] => (Scope::new(), Want::Number),
+            ["stars", "types", _, "adjust", _] => (Scope::new().with("type", Ty::str()), Want::Cond),
+            ["stars", rest @ ..] => {
+                let scope = Scope::new().with("type", Ty::str());
+                match rest {
+                    ["classes", _, "roll"] => (scope.pending(&["class"]), Want::Number),
+                    ["classes", _, "adjust", _] => (scope.with("class", Ty::str()), Want::Cond),
+                    _ => (scope.with("class", Ty::str()), Want::Number),
+                }
+            }
+            ["orbits", "moons", rest @ ..] => {
+                let scope = Scope::new().with("moon", Ty::COUNT);
+                match rest {
+                    ["count"] => (Scope::new(), Want::Number),
+                    ["size"] => (scope, Want::Number),
+                    ["orbit"] => (scope.with("size", Ty::COUNT), Want::Number),
+                    _ => {
+                        let scope = scope.with("size", Ty::COUNT).with("orbit", Ty::COUNT);
+                        (scope, want(rest.last() == Some(&"when")))
+                    }
+                }
+            }
+            ["orbits", rest @ ..] => {
+                let mut scope = Scope::new().with("type", Ty::str()).with("class", Ty::str());
+                if rest != ["count"] {
+                    scope.set("orbit", Ty::COUNT);
+                }
+                match rest {
+                    ["mainworld"] => (scope, Want::Cond),
+                    [.., "giant"] => (scope, Want::Text),
+                    _ => (scope, Want::Number),
+                }
+            }
+            ["trade_goods", ..] => (
+                Scope::new().with("pop", self.digit_ty("pop")).with("tech", Ty::Int(0, 15)),
+                Want::Number,
+            ),
+            _ => (Scope::new(), Want::Number),
+        }
+    }
+
+    pub fn trade_codes(&self, ctx: &Context) -> Vec<String> {
+        self.trade
+            .iter()
+            .filter(|(_, node)| node.is_true(ctx))
+            .map(|(code, _)| code.clone())
+            .collect()
+    }
+
+    /// Digit `n` of the `hex` alphabet, clamped to its last character.
+    pub fn hex_digit(&self, n: i64) -> char {
+        let hex = self.data.get("hex").and_then(|h| h.as_str()).unwrap_or("0123456789ABCDEF");
+        let digits: Vec<char> = hex.chars().collect();
+        digits.get(n.max(0) as usize).or(digits.last()).copied().unwrap_or('?')
+    }
+
+    /// A body's UWP code per `uwp_layout`: numbers as `hex` digits, strings (the
+    /// starport) as they are, separators verbatim. A digit reading a variable `ctx`
+    /// lacks prints as `.`, so partial bodies (a hostile moon) share the layout.
+    pub fn uwp_code(&self, ctx: &Context) -> String {
+        let mut out = String::new();
+        for (i, entry) in self.layout.iter().enumerate() {
+            match self.exprs.get(&format!("uwp_layout/{i}")) {
+                None => out.push_str(entry),
+                Some(n) if !n.bound_in(ctx) => out.push('.'),
+                Some(n) => match n.eval(ctx) {
+                    Value::Str(s) => out.push_str(&s),
+                    v => out.push(self.hex_digit(v.as_int())),
+                },
+            }
+        }
+        out
+    }
+
+    /// How many characters a UWP takes under `uwp_layout`, for lining up columns.
+    pub fn uwp_width(&self) -> usize {
+        self.uwp_code(&Context::new()).chars().count()
+    }
+
+    /// The raw starport roll (`starport.roll`, default 2D), taken once size through
+    /// climate are known — before Population, so a population DM goes in `pop_dm`.
+    pub fn starport_roll(&self, ctx: &Context) -> i64 {
+        match self.exprs.get("starport/roll") {
+            Some(n) => n.eval(ctx).as_int(),
+            None => rng::roll(2, 6).unwrap_or(0) as i64,
+        }
+    }
+
+    /// `starport.pop_dm` for a population (the list's ends repeat); 0 without one.
+    pub fn starport_pop_dm(&self, pop: i64) -> i64 {
+        match self.data.get("starport").and_then(|s| s.get("pop_dm")).and_then(|d| d.as_sequence()) {
+            Some(arr) if !arr.is_empty() => {
+                let i = pop.clamp(0, (arr.len() - 1) as i64) as usize;
+                arr[i].as_i64().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    /// The port class for a (modified) roll: `starport.table`, indexed from 0 and
+    /// clamped to its ends.
+    pub fn starport(&self, roll: i64) -> String {
+        match self
+            .data
+            .get("starport")
+            .and_then(|s| s.get("table"))
+            .and_then(|t| t.as_sequence())
+        {
+            Some(arr) if !arr.is_empty() => {
+                let idx = roll.clamp(0, (arr.len() - 1) as i64) as usize;
+                arr[idx].as_str().unwrap_or("X").to_string()
+            }
+            _ => "X".to_string(),
+        }
+    }
+
+    /// Whether `starport.facilities` declares this class.
+    pub fn has_facilities(&self, port: &str) -> bool {
+        self.data
+            .get("starport")
+            .and_then(|s| s.get("facilities"))
+            .and_then(|f| f.get(port))
+            .is_some()
+    }
+
+    /// A text facility (fuel/shipyard/repair) of a starport class: the plain string,
+    /// or the first matching `{ when, text }` row. Empty when the class is undeclared.
+    pub fn facility_text(&self, port: &str, key: &str, ctx: &Context) -> String {
+        let rows = self
+            .data
+            .get("starport")
+            .and_then(|s| s.get("facilities"))
+            .and_then(|f| f.get(port))
+            .and_then(|c| c.get(key));
+        self.first_row(&format!("starport/facilities/{port}/{key}"), rows, ctx)
+    }
+
+    /// A numeric facility (berthing/highport/downport) of a starport class; booleans
+    /// evaluate to 1/0. None when the class or field is undeclared.
+    pub fn facility_value(&self, port: &str, key: &str, ctx: &Context) -> Option<i64> {
+        self.exprs
+            .get(&format!("starport/facilities/{port}/{key}"))
+            .map(|n| n.eval(ctx).as_int())
+    }
+
+    pub fn tech_dm(&self, ctx: &Context) -> i64 {
+        let t = match self.data.get("tech_dm") {
+            Some(v) => v,
+            None => return 0,
+        };
+        let mut dm = 0i64;
+        if let Some(Value::Str(p)) = ctx.get("port") {
+            if let Some(v) = t.get("port").and_then(|m| m.get(p.as_str())) {
+                dm += v.as_i64().unwrap_or(0);
+            }
+        }
+        for k in ["size", "atmo", "hydro", "pop", "gov"] {
+            if let Some(arr) = t.get(k).and_then(|a| a.as_sequence()) {
+                let i = ctx.get(k).map(|v| v.as_int()).unwrap_or(0);
+                if i >= 0 && (i as usize) < arr.len() {
+                    dm += arr[i as usize].as_i64().unwrap_or(0);
+                }
+            }
+        }
+        dm
+    }
+
+    /// Base kinds in YAML order (the section-wide `op` key excluded).
+    pub fn base_kinds(&self) -> Vec<String> {
+        self.data
+            .get("bases")
+            .and_then(|b| b.as_mapping())
+            .map(|m| {
+                m.iter()
+                    .filter_map(|(k, _)| k.as_str())
+                    .filter(|k| *k != "op")
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// A base kind's spec. The compact form (`naval: { A: 6, B: 5 }`) is just the
+    /// per-port threshold map; the full form carries `threshold:` plus roll/when/dm.
+    fn base_spec(&self, kind: &str) -> Option<&Yaml> {
+        self.data.get("bases").and_then(|b| b.get(kind))
+    }
+
+    pub fn base_threshold(&self, kind: &str, port: &str) -> Option<i64> {
+        let spec = self.base_spec(kind)?;
+        match spec.get("threshold") {
+            Some(Yaml::Number(n)) => n.as_i64(),
+            Some(t) => t.get(port).and_then(|v| v.as_i64()),
+            None => spec.get(port).and_then(|v| v.as_i64()),
+        }
+    }
+
+    /// The report letter a kind declares with `code:` (custom kinds need one).
+    pub fn base_code(&self, kind: &str) -> Option<char> {
+        self.base_spec(kind)
+            .and_then(|s| s.get("code"))
+            .and_then(|c| c.as_str())
+            .and_then(|c| c.chars().next())
+    }
+
+    /// The SVG glyph a ruleset assigns to the base whose code is `code`.
+    pub fn base_glyph(&self, code: char) -> Option<String> {
+        self.base_kinds().iter().find_map(|k| {
+            let spec = self.base_spec(k)?;
+            if Base::from_kind(k, self.base_code(k))?.to_code() != code.to_string().as_str() {
+                return None;
+            }
+            spec.get("glyph").and_then(|g| g.as_str()).map(String::from)
+        })
+    }
+
+    pub fn base_meets(&self, roll: i64, threshold: i64) -> bool {
+        let op = self
+            .data
+            .get("bases")
+            .and_then(|b| b.get("op"))
+            .and_then(|o| o.as_str())
+            .unwrap_or("<=");
+        meets(op, roll, threshold)
+    }
+
+    /// Roll one base kind for a world: its `when` gate must hold, then `roll` (2D by
+    /// default) plus every matching DM is compared against the port's threshold using
+    /// the kind's `op` (else the section's). Trade codes are in scope as 1/0 flags.
+    pub fn base_check(&self, kind: &str, ctx: &Context) -> bool {
+        let port = match ctx.get("port") {
+            Some(Value::Str(p)) => p.clone(),
+            _ => return false,
+        };
+        let Some(threshold) = self.base_threshold(kind, &port) else {
+            return false;
+        };
+        if let Some(gate) = self.exprs.get(&format!("bases/{kind}/when")) {
+            if !gate.is_true(ctx) {
+                return false;
+            }
+        }
+        let mut roll = match self.exprs.get(&format!("bases/{kind}/roll")) {
+            Some(n) => n.eval(ctx).as_int(),
+            None => rng::roll_2d6().unwrap_or(7) as i64,
+        };
+        let rows = self
+            .base_spec(kind)
+            .and_then(|s| s.get("dm"))
+            .and_then(|d| d.as_sequence())
+            .map_or(0, |d| d.len());
+        for i in 0..rows {
+            let hit = self
+                .exprs
+                .get(&format!("bases/{kind}/dm/{i}/when"))
+                .is_none_or(|n| n.is_true(ctx));
+            if hit {
+                if let Some(dm) = self.exprs.get(&format!("bases/{kind}/dm/{i}/dm")) {
+                    roll += dm.eval(ctx).as_int();
+                }
+            }
+        }
+        match self.base_spec(kind).and_then(|s| s.get("op")).and_then(|o| o.as_str()) {
+            Some(op) => meets(op, roll, threshold),
+            None => self.base_meets(roll, threshold),
+        }
+    }
+
+    /// World Trade Number from the `trade.wtn` formula (0 without one).
+    pub fn trade_wtn(&self, ctx: &Context) -> i64 {
+        self.exprs
+            .get("trade/wtn")
+            .map(|n| n.eval(ctx).as_int().max(0))
+            .unwrap_or(0)
+    }
+
+    /// The farthest jump at which two worlds still trade (`trade.max_jump`, default 4).
+    pub fn trade_max_jump(&self) -> i64 {
+        self.trade_get("max_jump").and_then(|v| v.as_i64()).unwrap_or(4)
+    }
+
+    /// The BTN penalty for a jump distance: `trade.distance` indexed by jump, the last
+    /// entry repeating beyond the end.
+    pub fn trade_distance_dm(&self, jump: i64) -> i64 {
+        match self.trade_get("distance").and_then(|d| d.as_sequence()) {
+            Some(arr) if !arr.is_empty() => {
+                let i = jump.clamp(0, (arr.len() - 1) as i64) as usize;
+                arr[i].as_i64().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Complementary trade-code pairs: +1 BTN when one world has the first code and
+    /// the other the second.
+    pub fn trade_pairs(&self) -> Vec<(String, String)> {
+        self.trade_get("pairs")
+            .and_then(|p| p.as_sequence())
+            .map(|p| {
+                p.iter()
+                    .filter_map(|pair| {
+                        let a = pair.get(0)?.as_str()?;
+                        let b = pair.get(1)?.as_str()?;
+                        Some((a.to_string(), b.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// How far a BTN may exceed the smaller of its two WTNs (`trade.cap`).
+    pub fn trade_cap(&self) -> Option<i64> {
+        self.trade_get("cap").and_then(|v| v.as_i64())
+    }
+
+    /// The route class for a BTN: the first of `trade.routes` (major, intermediate,
+    /// minor, …, in YAML order) whose minimum it reaches.
+    pub fn trade_route_class(&self, btn: i64) -> Option<String> {
+        self.trade_get("routes")
+            .and_then(|r| r.as_mapping())?
+            .iter()
+            .find(|(_, min)| min.as_i64().is_some_and(|m| btn >= m))
+            .and_then(|(k, _)| k.as_str())
+            .map(String::from)
+    }
+
+    fn trade_get(&self, key: &str) -> Option<&Yaml> {
+        self.data.get("trade").and_then(|t| t.get(key))
+    }
+
+    /// The speculative-trade goods table, in YAML order.
+    pub fn trade_goods(&self) -> &[TradeGood] {
+        &self.goods
+    }
+
+    /// Lot size in tons for the `i`th trade good (its `tons` formula).
+    pub fn trade_good_tons(&self, i: usize, ctx: &Context) -> i64 {
+        self.exprs
+            .get(&format!("trade_goods/{i}/tons"))
+            .map(|n| n.eval(ctx).as_int().max(0))
+            .unwrap_or(0)
+    }
+
+    /// The broker's unmodified roll (`speculation.roll`, default 2d6).
+    pub fn speculation_roll(&self) -> i64 {
+        match self.exprs.get("speculation/roll") {
+            Some(n) => n.eval(&Context::new()).as_int(),
+            None => rng::roll_2d6().unwrap_or(7) as i64,
+        }
+    }
+
+    /// Percent of base price for a modified broker roll on the `purchase` or `sale`
+    /// table. Entry 0 is roll `speculation.min`; rolls off either end clamp.
+    pub fn speculation_percent(&self, table: &str, roll: i64) -> i64 {
+        let spec = self.data.get("speculation");
+        let min = spec.and_then(|s| s.get("min")).and_then(|m| m.as_i64()).unwrap_or(0);
+        match spec.and_then(|s| s.get(table)).and_then(|t| t.as_sequence()) {
+            Some(arr) if !arr.is_empty() => {
+                let i = (roll - min).clamp(0, (arr.len() - 1) as i64) as usize;
+                arr[i].as_i64().unwrap_or(100)
+            }
+            _ => 100,
+        }
+    }
+
+    /// Whether the ruleset declares a `traffic:` section.
+    pub fn has_traffic(&self) -> bool {
+        self.data.get("traffic").and_then(|m| m.as_mapping()).is_some()
+    }
+
+    /// Evaluate one `traffic.<name>` formula (`lots/<class>` for a lot size); 0 when
+    /// the ruleset omits it.
+    pub fn traffic_step(&self, name: &str, ctx: &Context) -> i64 {
+        self.exprs
+            .get(&format!("traffic/{name}"))
+            .map(|n| n.eval(ctx).as_int())
+            .unwrap_or(0)
+    }
+
+    /// The `traffic.passengers` or `traffic.freight` classes and their DMs, in YAML order.
+    pub fn traffic_classes(&self, group: &str) -> Vec<(String, i64)> {
+        self.data
+            .get("traffic")
+            .and_then(|t| t.get(group))
+            .and_then(|g| g.as_mapping())
+            .map(|m| {
+                m.iter()
+                    .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_i64()?)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Dice of passengers or lots for a modified traffic roll. Entry 0 of
+    /// `traffic.table` is roll `traffic.min`; rolls off either end clamp.
+    pub fn traffic_dice(&self, roll: i64) -> i64 {
+        let tr = self.data.get("traffic");
+        let min = tr.and_then(|s| s.get("min")).and_then(|m| m.as_i64()).unwrap_or(0);
+        match tr.and_then(|s| s.get("table")).and_then(|t| t.as_sequence()) {
+            Some(arr) if !arr.is_empty() => {
+                let i = (roll - min).clamp(0, (arr.len() - 1) as i64) as usize;
+                arr[i].as_i64().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether a world anchors the X-boat network (`xboat.hub`); false without one.
+    pub fn xboat_hub(&self, ctx: &Context) -> bool {
+        self.exprs.get("xboat/hub").is_some_and(|n| n.is_true(ctx))
+    }
+
+    /// The longest X-boat link in parsecs (`xboat.max_jump`, default 4).
+    pub fn xboat_max_jump(&self) -> i64 {
+        self.data
+            .get("xboat")
+            .and_then(|x| x.get("max_jump"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(4)
+    }
+
+    /// Percent of the hull a jump burns per parsec (`fuel.per_parsec`, default 10).
+    pub fn fuel_per_parsec(&self) -> i64 {
+        self.data
+            .get("fuel")
+            .and_then(|f| f.get("per_parsec"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(10)
+    }
+
+    /// Cr per ton of `refined`, `unrefined` or `wilderness` fuel (`fuel.price`).
+    pub fn fuel_price(&self, kind: &str) -> i64 {
+        let default = match kind {
+            "refined" => 500,
+            "unrefined" => 100,
+            _ => 0,
+        };
+        self.data
+            .get("fuel")
+            .and_then(|f| f.get("price"))
+            .and_then(|p| p.get(kind))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(default)
+    }
+
+    /// Whether the ruleset declares a `military:` section (no section = no profiles).
+    pub fn has_military(&self) -> bool {
+        self.data.get("military").and_then(|m| m.as_mapping()).is_some()
+    }
+
+    /// Evaluate one `military.<name>` formula; None when the ruleset omits it.
+    pub fn military_step(&self, name: &str, ctx: &Context) -> Option<i64> {
+        self.exprs
+            .get(&format!("military/{name}"))
+            .map(|n| n.eval(ctx).as_int())
+    }
+
+    /// Whether the ruleset declares a `culture:` section.
+    pub fn has_culture(&self) -> bool {
+        self.data.get("culture").and_then(|m| m.as_mapping()).is_some()
+    }
+
+    /// The attitude to offworlders: the `text` of the first `culture.offworlders` row
+    /// whose `when` holds (a row without `when` always matches).
+    pub fn culture_offworlders(&self, ctx: &Context) -> String {
+        let rows = self.data.get("culture").and_then(|c| c.get("offworlders"));
+        self.first_row("culture/offworlders", rows, ctx)
+    }
+
+    /// The `text` of the first row in `rows` whose compiled `<path>/<i>` condition
+    /// holds; a row without `when` always matches. A plain string is its own answer.
+    fn first_row(&self, path: &str, rows: Option<&Yaml>, ctx: &Context) -> String {
+        match rows {
+            Some(Yaml::String(s)) => s.clone(),
+            Some(Yaml::Sequence(rows)) => rows
+                .iter()
+                .enumerate()
+                .find(|(i, _)| {
+                    self.exprs
+                        .get(&format!("{path}/{i}"))
+                        .is_none_or(|n| n.is_true(ctx))
+                })
+                .and_then(|(_, row)| row.get("text").and_then(|t| t.as_str()))
+                .unwrap_or("")
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// One culture list (religions/languages/quirks): every `always` row whose `when`
+    /// holds, then `count` distinct picks from `table`.
+    pub fn culture_list(&self, kind: &str, ctx: &Context) -> Vec<String> {
+        let spec = match self.data.get("culture").and_then(|c| c.get(kind)) {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+        let mut out: Vec<String> = Vec::new();
+        if let Some(rows) = spec.get("always").and_then(|r| r.as_sequence()) {
+            for (i, row) in rows.iter().enumerate() {
+                let hit = self
+                    .exprs
+                    .get(&format!("culture/{kind}/always/{i}"))
+                    .is_none_or(|n| n.is_true(ctx));
+                if let (true, Some(text)) = (hit, row.get("text").and_then(|t| t.as_str())) {
+                    out.push(text.to_string());
+                }
+            }
+        }
+        let mut table: Vec<String> = spec
+            .get("table")
+            .and_then(|t| t.as_sequence())
+            .map(|t| t.iter().filter_map(|x| x.as_str()).map(String::from).collect())
+            .unwrap_or_default();
+        table.retain(|t| !out.contains(t));
+        let count = self
+            .exprs
+            .get(&format!("culture/{kind}/count"))
+            .map(|n| n.eval(ctx).as_int())
+            .unwrap_or(0);
+        for _ in 0..count.max(0) {
+            if table.is_empty() {
+                break;
+            }
+            let idx = rng::roll_range(table.len()).unwrap_or(0);
+            out.push(table.remove(idx));
+        }
+        out
+    }
+
+    /// The genres this ruleset declares (`genres:`), in YAML order.
+    pub fn genres(&self) -> Vec<String> {
+        self.data
+            .get("genres")
+            .and_then(|g| g.as_mapping())
+            .map(|g| g.keys().filter_map(|k| k.as_str()).map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    fn genre<'a>(&self, genre: &'a str) -> &'a str {
+        match self.data.get("genres").and_then(|g| g.get(genre)) {
+            Some(_) => genre,
+            None => "normal",
+        }
+    }
+
+    /// The `stars.types` table a `genre`'s primaries are rolled on (its `census`; an
+    /// undeclared genre uses `normal`).
+    pub fn star_census(&self, genre: &str) -> String {
+        let genre = self.genre(genre);
+        match self.exprs.get(&format!("genres/{genre}/census")) {
+            Some(node) => match node.eval(&Context::new()) {
+                Value::Str(s) => s,
+                v => v.as_int().to_string(),
+            },
+            None => self
+                .data
+                .get("genres")
+                .and_then(|g| g.get(genre))
+                .and_then(|g| g.get("census"))
+                .and_then(|t| t.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        }
     }
 
-    pub fn starport(&self, roll: i64) -> String {
-        match self
+    /// A genre's realism pass over one UWP digit (`atmo` or `hydro`): its rows
+    /// `set` or `delta` the value in order, each `when` seeing the value so far,
+    /// clamped to the step's `clamp`.
+    pub fn genre_adjust(&self, genre: &str, step: &str, ctx: &Context, mut val: i64) -> i64 {
+        let genre = self.genre(genre);
+        let rows = self
             .data
-            .get("starport")
-            .and_then(|s| s.get("table"))
-            .and_then(|t| t.as_sequence())
-        {
-            Some(arr) if !arr.is_empty() => {
-                let idx = roll.clamp(0, (arr.len() - 1) as i64) as usize;
-                arr[idx].as_str().unwrap_or("X").to_string()
+            .get("genres")
+            .and_then(|g| g.get(genre))
+            .and_then(|g| g.get(step))
+            .and_then(|r| r.as_sequence());
+        let Some(rows) = rows else { return val };
+        for (i, row) in rows.iter().enumerate() {
+            let mut cw = ctx.clone();
+            cw.insert(step.to_string(), Value::Int(val));
+            let Some(w) = self.exprs.get(&format!("genres/{genre}/{step}/{i}")) else { continue };
+            if w.is_true(&cw) {
+                if let Some(set) = row.get("set").and_then(|s| s.as_i64()) {
+                    val = set;
+                } else if let Some(delta) = row.get("delta").and_then(|d| d.as_i64()) {
+                    val += delta;
+                }
             }
-            _ => "X".to_string(),
+        }
+        match self.digit_ty(step) {
+            Ty::Int(lo, hi) => val.clamp(lo, hi),
+            _ => val,
         }
     }
 
-    pub fn tech_dm(&self, ctx: &Context) -> i64 {
-        let t = match self.data.get("tech_dm") {
-            Some(v) => v,
-            None => return 0,
-        };
-        let mut dm = 0i64;
-        if let Some(Value::Str(p)) = ctx.get("port") {
-            if let Some(v) = t.get("port").and_then(|m| m.get(p.as_str())) {
-                dm += v.as_i64().unwrap_or(0);
+    /// A genre's population DM (`pop_dm`) or starport-roll DM (`port_dm`); 0 when
+    /// the genre has none.
+    pub fn genre_dm(&self, genre: &str, key: &str, ctx: &Context) -> i64 {
+        self.exprs
+            .get(&format!("genres/{}/{key}", self.genre(genre)))
+            .map(|n| n.eval(ctx).as_int())
+            .unwrap_or(0)
+    }
+
+    /// This ruleset with `extra` merged over it (as a child file would be), then
+    /// rebuilt and validated — e.g. genres declared in the project config.
+    pub fn overlay(self, extra: Yaml) -> Result<Ruleset, String> {
+        let (mut origins, mut extra_from) = (self.origins, Origins::new());
+        leaf_paths(&extra, "", "overlay", &mut extra_from);
+        let data = deep_merge_traced(self.data, &mut origins, extra, &extra_from, "");
+        let mut rs = Ruleset::build(self.name, data)?;
+        rs.root = self.root;
+        rs.origins = origins;
+        rs.validate()?;
+        Ok(rs)
+    }
+
+    /// Roll `stars.<group>.<table>` ("types" or "classes"): `roll` picks the row
+    /// (the first is `from`, clamped to the ends), an entry naming another table of
+    /// the group rolls that instead, and `adjust` rows then `set` the result when
+    /// their `when` holds (with it in scope as `type`/`class`).
+    pub fn star_roll(&self, group: &str, table: &str, ctx: &Context) -> Result<String, String> {
+        let var = if group == "types" { "type" } else { "class" };
+        let mut table = table.to_string();
+        for _ in 0..8 {
+            let spec = self
+                .data
+                .get("stars")
+                .and_then(|s| s.get(group))
+                .and_then(|g| g.get(table.as_str()))
+                .ok_or_else(|| format!("ruleset {:?}: no table `stars.{group}.{table}`", self.name))?;
+            let Some(row) = self.table_row(&format!("stars/{group}/{table}"), spec, ctx) else {
+                return Err(format!("ruleset {:?}: stars.{group}.{table}: empty table", self.name));
+            };
+            let mut entry = row.as_str().unwrap_or_default().to_string();
+            if !star_code(group, &entry) {
+                table = entry;
+                continue;
             }
-        }
-        for k in ["size", "atmo", "hydro", "pop", "gov"] {
-            if let Some(arr) = t.get(k).and_then(|a| a.as_sequence()) {
-                let i = ctx.get(k).map(|v| v.as_int()).unwrap_or(0);
-                if i >= 0 && (i as usize) < arr.len() {
-                    dm += arr[i as usize].as_i64().unwrap_or(0);
+            if let Some(adj) = spec.get("adjust").and_then(|a| a.as_sequence()) {
+                for (i, a) in adj.iter().enumerate() {
+                    let mut cw = ctx.clone();
+                    cw.insert(var.to_string(), Value::Str(entry.clone()));
+                    let Some(w) = self.exprs.get(&format!("stars/{group}/{table}/adjust/{i}")) else {
+                        continue;
+                    };
+                    if w.is_true(&cw) {
+                        if let Some(set) = a.get("set").and_then(|s| s.as_str()) {
+                            entry = set.to_string();
+                        }
+                    }
                 }
             }
+            return Ok(entry);
         }
-        dm
+        Err(format!("ruleset {:?}: stars.{group}.{table}: tables refer to each other", self.name))
     }
 
-    pub fn base_threshold(&self, kind: &str, port: &str) -> Option<i64> {
-        self.data
-            .get("bases")
-            .and_then(|b| b.get(kind))
-            .and_then(|m| m.get(port))
-            .and_then(|v| v.as_i64())
+    /// One row of a rolled table (`stars:`, `orbits:`): the formula at `path/roll`
+    /// picks it, `from` being the roll of the first row, clamped to the ends.
+    fn table_row<'a>(&self, path: &str, spec: &'a Yaml, ctx: &Context) -> Option<&'a Yaml> {
+        let rows = spec.get("table").and_then(|t| t.as_sequence()).filter(|r| !r.is_empty())?;
+        let roll = self
+            .exprs
+            .get(&format!("{path}/roll"))
+            .map(|n| n.eval(ctx).as_int())
+            .unwrap_or(0);
+        let from = spec.get("from").and_then(|f| f.as_i64()).unwrap_or(0);
+        rows.get((roll - from).clamp(0, rows.len() as i64 - 1) as usize)
     }
 
-    pub fn base_meets(&self, roll: i64, threshold: i64) -> bool {
-        let op = self
+    /// The `stars.classes` table for a spectral type: its own, else `default`.
+    pub fn star_class_table(&self, star_type: &str) -> String {
+        let classes = self.data.get("stars").and_then(|s| s.get("classes"));
+        match classes.and_then(|c| c.get(star_type)) {
+            Some(_) => star_type.to_string(),
+            None => "default".to_string(),
+        }
+    }
+
+    /// A `stars:` formula (`subtype`, `companions/count`, `companions/orbit`); 0 when
+    /// the ruleset leaves it out.
+    pub fn star_value(&self, key: &str, ctx: &Context) -> i64 {
+        self.exprs
+            .get(&format!("stars/{key}"))
+            .map(|n| n.eval(ctx).as_int())
+            .unwrap_or(0)
+    }
+
+    /// Mass (solar masses), luminosity (solar) and temperature (K) of a star:
+    /// `stars.physical` by type, then `stars.class_factors` by class (`mass` and
+    /// `luminosity` multiply, `set_mass` replaces). None without a physical entry.
+    pub fn star_physical(&self, star_type: &str, class: &str) -> Option<(f64, f64, u32)> {
+        let stars = self.data.get("stars")?;
+        let p = stars.get("physical")?.get(star_type)?;
+        let num = |v: &Yaml, key: &str| v.get(key).and_then(|x| x.as_f64());
+        let mut mass = num(p, "mass")?;
+        let mut lum = num(p, "luminosity")?;
+        let temp = num(p, "temperature")? as u32;
+        if let Some(f) = stars.get("class_factors").and_then(|c| c.get(class)) {
+            lum *= num(f, "luminosity").unwrap_or(1.0);
+            mass *= num(f, "mass").unwrap_or(1.0);
+            if let Some(m) = num(f, "set_mass") {
+                mass = m;
+            }
+        }
+        Some((mass, lum, temp))
+    }
+
+    /// How many orbits a star has: `orbits.count` plus `orbits.count_dm` for its
+    /// spectral type and luminosity class.
+    pub fn orbit_count(&self, star_type: &str, class: &str) -> i64 {
+        let mut ctx = Context::new();
+        ctx.insert("type".to_string(), Value::Str(star_type.to_string()));
+        ctx.insert("class".to_string(), Value::Str(class.to_string()));
+        let dms = self.data.get("orbits").and_then(|o| o.get("count_dm"));
+        let dm = |group: &str, key: &str| {
+            dms.and_then(|d| d.get(group)).and_then(|g| g.get(key)).and_then(|v| v.as_i64()).unwrap_or(0)
+        };
+        self.orbit_value("count", &ctx) + dm("type", star_type) + dm("class", class)
+    }
+
+    /// An `orbits:` formula by path (`mainworld`, `zones/inner/hostile/atmo`,
+    /// `moons/size`, ...); 0 when the ruleset leaves it out.
+    pub fn orbit_value(&self, key: &str, ctx: &Context) -> i64 {
+        self.exprs
+            .get(&format!("orbits/{key}"))
+            .map(|n| n.eval(ctx).as_int())
+            .unwrap_or(0)
+    }
+
+    /// Whether a biozone orbit holds the mainworld (`orbits.mainworld`).
+    pub fn orbit_has_mainworld(&self, ctx: &Context) -> bool {
+        self.exprs.get("orbits/mainworld").is_some_and(|n| n.is_true(ctx))
+    }
+
+    /// What fills an orbit in `zone` (inner, habitable, outer): one of ORBIT_BODIES,
+    /// with a plain `gas_giant` sized by the zone's `giant` formula.
+    pub fn orbit_body(&self, zone: &str, ctx: &Context) -> String {
+        let path = format!("orbits/zones/{zone}");
+        let Some(spec) = self.data.get("orbits").and_then(|o| o.get("zones")).and_then(|z| z.get(zone)) else {
+            return "empty".to_string();
+        };
+        let body = self.table_row(&path, spec, ctx).and_then(|r| r.as_str()).unwrap_or("empty");
+        if body != "gas_giant" {
+            return body.to_string();
+        }
+        match self.exprs.get(&format!("{path}/giant")).map(|n| n.eval(ctx)) {
+            Some(Value::Str(s)) if s == "large" => "large_giant".to_string(),
+            _ => "small_giant".to_string(),
+        }
+    }
+
+    /// A moon's orbital radius (in planetary diameters): the first
+    /// `orbits.moons.bands` row whose `when` holds (or has none) rolls its table.
+    pub fn moon_radius(&self, ctx: &Context) -> i64 {
+        let bands = self
             .data
-            .get("bases")
-            .and_then(|b| b.get("op"))
-            .and_then(|o| o.as_str())
-            .unwrap_or("<=");
-        match op {
-            ">=" => roll >= threshold,
-            ">" => roll > threshold,
-            "<" => roll < threshold,
-            "==" => roll == threshold,
-            _ => roll <= threshold,
+            .get("orbits")
+            .and_then(|o| o.get("moons"))
+            .and_then(|m| m.get("bands"))
+            .and_then(|b| b.as_sequence());
+        for (i, band) in bands.into_iter().flatten().enumerate() {
+            let path = format!("orbits/moons/bands/{i}");
+            if self.exprs.get(&format!("{path}/when")).is_some_and(|w| !w.is_true(ctx)) {
+                continue;
+            }
+            return self.table_row(&path, band, ctx).and_then(|r| r.as_i64()).unwrap_or(0);
         }
+        0
     }
 
+    /// The module named for `slot` (default `t5`), lowercased — except a
+    /// `script:<path>`, whose path must stay inside the project directory.
     pub fn module_for(&self, slot: &str) -> Result<String, String> {
         let name = self
             .data
             .get("modules")
             .and_then(|m| m.get(slot))
             .and_then(|v| v.as_str())
-            .map(|s| s.to_lowercase())
+            .map(str::trim)
             .filter(|s| !s.is_empty())
-            .unwrap_or_else(|| "t5".to_string());
-        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            .unwrap_or("t5");
+        if let Some(path) = name.strip_prefix("script:") {
+            let path_ok = path.chars().all(|c| c.is_alphanumeric() || "_-./".contains(c))
+                && !path.starts_with('/')
+                && !path.split('/').any(|p| p.is_empty() || p == "..");
+            if !path_ok {
+                return Err(format!(
+                    "ruleset {:?}: modules.{slot}: bad script path {path:?} (want a path inside the project, e.g. `script:rules/x.rhai`)",
+                    self.name
+                ));
+            }
+            return Ok(name.to_string());
+        }
+        let name = name.to_lowercase();
+        if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
             return Err(format!(
                 "ruleset {:?}: bad module name {name:?} for {slot}",
                 self.name
@@ -235,13 +1547,13 @@ impl Ruleset {
             Some(s) => s,
             None => return 0,
         };
-        if let Some(zw) = self.uwp_cache.get(&format!("uwp/{name}/zero_when")) {
+        if let Some(zw) = self.exprs.get(&format!("uwp/{name}/zero_when")) {
             if zw.is_true(ctx) {
                 return 0;
             }
         }
         let mut val = self
-            .uwp_cache
+            .exprs
             .get(&format!("uwp/{name}/roll"))
             .map(|n| n.eval(ctx).as_int())
             .unwrap_or(0);
@@ -249,9 +1561,9 @@ impl Ruleset {
         if spec.get("reroll").is_some() {
             let mut cw = ctx.clone();
             cw.insert(name.to_string(), Value::Int(val));
-            if let Some(w) = self.uwp_cache.get(&format!("uwp/{name}/reroll/when")) {
+            if let Some(w) = self.exprs.get(&format!("uwp/{name}/reroll/when")) {
                 if w.is_true(&cw) {
-                    if let Some(with) = self.uwp_cache.get(&format!("uwp/{name}/reroll/with")) {
+                    if let Some(with) = self.exprs.get(&format!("uwp/{name}/reroll/with")) {
                         val = with.eval(&cw).as_int();
                     }
                 }
@@ -261,7 +1573,7 @@ impl Ruleset {
             for (i, a) in adj.iter().enumerate() {
                 let mut cw = ctx.clone();
                 cw.insert(name.to_string(), Value::Int(val));
-                if let Some(w) = self.uwp_cache.get(&format!("uwp/{name}/adjust/{i}")) {
+                if let Some(w) = self.exprs.get(&format!("uwp/{name}/adjust/{i}")) {
                     if w.is_true(&cw) {
                         if let Some(set) = a.get("set").and_then(|s| s.as_i64()) {
                             val = set;
@@ -283,21 +1595,179 @@ impl Ruleset {
     }
 }
 
-/// Read one ruleset file and fold in its parent (extends), child wins.
-fn load_merged(name: &str, project_root: &str, seen: &mut Vec<String>) -> Result<Yaml, String> {
-    if seen.iter().any(|n| n == name) {
-        return Err(format!("ruleset {name:?} has a cyclic extends chain"));
+/// The `orbits.zones` tables, innermost first.
+const ORBIT_ZONES: [&str; 3] = ["inner", "habitable", "outer"];
+
+/// What an `orbits.zones` table row can hold.
+const ORBIT_BODIES: [&str; 7] =
+    ["empty", "rockball", "belt", "hostile", "gas_giant", "small_giant", "large_giant"];
+
+/// Whether `code` is a result of a `stars.<group>` table (a spectral letter for
+/// `types`, a luminosity class for `classes`) rather than the name of another table.
+fn star_code(group: &str, code: &str) -> bool {
+    match group {
+        "types" => StarType::from_code(code).is_some(),
+        _ => StarSize::from_code(code).is_some(),
+    }
+}
+
+/// A bare identifier (a table name), as opposed to a formula.
+fn is_name(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn meets(op: &str, roll: i64, threshold: i64) -> bool {
+    match op {
+        ">=" => roll >= threshold,
+        ">" => roll > threshold,
+        "<" => roll < threshold,
+        "==" => roll == threshold,
+        _ => roll <= threshold,
+    }
+}
+
+/// The UWP layout when a ruleset declares none: Traveller's StSAHPGL-T.
+const DEFAULT_UWP_LAYOUT: [&str; 9] = ["port", "size", "atmo", "hydro", "pop", "gov", "law", "-", "tech"];
+
+/// A `uwp_layout` entry printed as-is: no letters, digits or `_` (e.g. `-`, ` `).
+fn is_separator(entry: &str) -> bool {
+    !entry.chars().any(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// The world variables in the order the generator sets them.
+const WORLD_VARS: [&str; 9] = ["size", "atmo", "hydro", "temp", "pop", "gov", "law", "port", "tech"];
+
+/// Military formulas in evaluation order; each sees the ones before it, and
+/// `navy`/`army` once `navy_share` is known.
+const MILITARY_STEPS: [&str; 6] = ["defence", "budget", "navy_share", "fleet", "max_ship_tl", "squadrons"];
+
+/// Starport facility fields that are formulas, and those that are text (or rows).
+const FACILITY_VALUES: [&str; 3] = ["berthing", "highport", "downport"];
+const FACILITY_TEXTS: [&str; 3] = ["fuel", "shipyard", "repair"];
+
+/// The source of a formula field: a string expression, or a bare YAML number or
+/// boolean (`true`/`false` become 1/0).
+fn expr_source(v: &Yaml) -> Option<String> {
+    match v {
+        Yaml::String(s) => Some(s.clone()),
+        Yaml::Number(n) => Some(n.to_string()),
+        Yaml::Bool(b) => Some(if *b { "1" } else { "0" }.to_string()),
+        _ => None,
+    }
+}
+
+/// The `tables:` section: each a list of numbers or strings, any entry optionally
+/// `{ value, weight }` for `pick` (weight 1 otherwise).
+fn parse_tables(data: &Yaml) -> Result<HashMap<String, Table>, String> {
+    let mut tables = HashMap::new();
+    let Some(m) = data.get("tables").and_then(|t| t.as_mapping()) else {
+        return Ok(tables);
+    };
+    for (k, v) in m.iter() {
+        let Some(name) = k.as_str() else { continue };
+        let rows = v.as_sequence().ok_or_else(|| format!("tables.{name}: expected a list"))?;
+        let mut table = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let (weight, value) = match row.get("value") {
+                Some(value) => (row.get("weight").and_then(|w| w.as_i64()).unwrap_or(1), value),
+                None => (1, row),
+            };
+            let value = match value {
+                Yaml::String(s) => Value::Str(s.clone()),
+                Yaml::Number(n) => Value::Int(
+                    n.as_i64().ok_or_else(|| format!("tables.{name}[{i}]: not a whole number"))?,
+                ),
+                Yaml::Bool(b) => Value::Int(*b as i64),
+                _ => return Err(format!("tables.{name}[{i}]: expected a number or string")),
+            };
+            table.push((weight, value));
+        }
+        tables.insert(name.to_string(), table);
+    }
+    Ok(tables)
+}
+
+/// Load a ruleset with everything it builds on: each `extends:` parent in order,
+/// then each `include:` mixin, then the file itself — later wins. The graph is
+/// flattened first (depth-first, each file once, bases before what builds on
+/// them), so a shared base is applied once and `key!:` replaces as in a single
+/// chain. `stack` is the chain being read; a cycle anywhere is reported in full.
+fn load_merged(name: &str, project_root: &str, stack: &mut Vec<String>) -> Result<(Yaml, Origins), String> {
+    let mut order = Vec::new();
+    linearize(name, project_root, stack, &mut order)?;
+    let mut merged = Yaml::Mapping(Default::default());
+    let mut from = Origins::new();
+    for (file, data) in order {
+        let mut own = Origins::new();
+        leaf_paths(&data, "", &file, &mut own);
+        merged = deep_merge_traced(merged, &mut from, data, &own, "");
+    }
+    t5_sections(&mut merged, &mut from);
+    Ok((merged, from))
+}
+
+/// Sections a ruleset may leave out entirely; they come from the built-in t5.
+const T5_SECTIONS: [&str; 3] = ["genres", "stars", "orbits"];
+
+/// Fill in each of T5_SECTIONS the merged ruleset lacks with t5's, traced to `t5`.
+/// A section the ruleset has (even in part) is left alone for `validate` to check.
+fn t5_sections(merged: &mut Yaml, from: &mut Origins) {
+    let Some(map) = merged.as_mapping_mut() else { return };
+    let missing: Vec<&str> = T5_SECTIONS.into_iter().filter(|k| !map.contains_key(*k)).collect();
+    if missing.is_empty() {
+        return;
+    }
+    let t5: Yaml = serde_yaml::from_str(builtin("t5").unwrap_or_default()).unwrap_or_default();
+    for key in missing {
+        if let Some(section) = t5.get(key) {
+            leaf_paths(section, key, "t5", from);
+            map.insert(key.into(), section.clone());
+        }
     }
-    seen.push(name.to_string());
+}
 
+/// Append `name` to `order` after the files it builds on, skipping any already there.
+fn linearize(
+    name: &str,
+    project_root: &str,
+    stack: &mut Vec<String>,
+    order: &mut Vec<(String, Yaml)>,
+) -> Result<(), String> {
+    if let Some(i) = stack.iter().position(|n| n == name) {
+        let cycle: Vec<&str> = stack[i..].iter().map(String::as_str).chain([name]).collect();
+        return Err(format!("ruleset {name:?}: cyclic extends/include: {}", cycle.join(" -> ")));
+    }
+    if order.iter().any(|(n, _)| n == name) {
+        return Ok(());
+    }
     let raw = read_rule_file(name, project_root)?;
-    let data: Yaml = serde_yaml::from_str(&raw).map_err(|e| format!("ruleset {name:?}: {e}"))?;
+    let mut data: Yaml = serde_yaml::from_str(&raw).map_err(|e| format!("ruleset {name:?}: {e}"))?;
+    let mut bases = Vec::new();
+    for key in ["extends", "include"] {
+        let list = data.as_mapping_mut().and_then(|m| m.remove(key));
+        bases.extend(rule_names(name, key, list)?);
+    }
+    stack.push(name.to_string());
+    for base in bases {
+        linearize(&base, project_root, stack, order)?;
+    }
+    stack.pop();
+    order.push((name.to_string(), data));
+    Ok(())
+}
 
-    if let Some(base) = data.get("extends").and_then(|v| v.as_str()) {
-        let parent = load_merged(base, project_root, seen)?;
-        return Ok(deep_merge(parent, data));
+/// The names under `extends:` or `include:`: one, or a list.
+fn rule_names(name: &str, key: &str, v: Option<Yaml>) -> Result<Vec<String>, String> {
+    let bad = || format!("ruleset {name:?}: `{key}:` wants a ruleset name or a list of them");
+    match v {
+        None => Ok(Vec::new()),
+        Some(Yaml::String(s)) => Ok(vec![s]),
+        Some(Yaml::Sequence(list)) => {
+            list.into_iter().map(|v| v.as_str().map(String::from).ok_or_else(bad)).collect()
+        }
+        Some(_) => Err(bad()),
     }
-    Ok(data)
 }
 
 fn read_rule_file(name: &str, project_root: &str) -> Result<String, String> {
@@ -312,27 +1782,75 @@ fn read_rule_file(name: &str, project_root: &str) -> Result<String, String> {
         .ok_or_else(|| format!("unknown ruleset {name:?} (no project rules/{name}.yml and no built-in)"))
 }
 
-/// Deep-merge child `b` over parent `a`. A child key ending in `!` replaces the
-/// parent value wholesale instead of deep-merging.
-fn deep_merge(a: Yaml, b: Yaml) -> Yaml {
+/// Deep-merge child `b` over parent `a`, keeping `from` (where each of `a`'s values
+/// came from) in step: wherever `b` sets a value, its `b_from` entries replace the
+/// parent's. A child key ending in `!` replaces the parent value wholesale instead
+/// of deep-merging.
+fn deep_merge_traced(a: Yaml, from: &mut Origins, b: Yaml, b_from: &Origins, path: &str) -> Yaml {
     match (a, b) {
         (Yaml::Mapping(mut am), Yaml::Mapping(bm)) => {
             for (k, bv) in bm {
-                if let Yaml::String(ks) = &k {
-                    if let Some(stripped) = ks.strip_suffix('!') {
-                        am.insert(Yaml::String(stripped.to_string()), bv);
-                        continue;
-                    }
-                }
+                let (k, replace) = match k.as_str().and_then(|s| s.strip_suffix('!')) {
+                    Some(stripped) => (Yaml::String(stripped.to_string()), true),
+                    None => (k, false),
+                };
+                let sub = origin_path(path, &k);
                 let merged = match am.get(&k) {
-                    Some(av) if av.is_mapping() && bv.is_mapping() => deep_merge(av.clone(), bv),
-                    _ => bv,
+                    Some(av) if !replace && av.is_mapping() && bv.is_mapping() => {
+                        deep_merge_traced(av.clone(), from, bv, b_from, &sub)
+                    }
+                    _ => {
+                        take_origins(from, b_from, &sub);
+                        bv
+                    }
                 };
                 am.insert(k, merged);
             }
             Yaml::Mapping(am)
         }
-        (_, b) => b,
+        (_, b) => {
+            take_origins(from, b_from, path);
+            b
+        }
+    }
+}
+
+/// Which file set each value of a loaded ruleset, by dotted YAML path
+/// (`starport.table`); a list is one value.
+pub type Origins = BTreeMap<String, String>;
+
+fn origin_path(path: &str, key: &Yaml) -> String {
+    let key = match key {
+        Yaml::String(s) => s.clone(),
+        other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+    };
+    if path.is_empty() { key } else { format!("{path}.{key}") }
+}
+
+fn under(p: &str, path: &str) -> bool {
+    path.is_empty() || p == path || p.strip_prefix(path).is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Replace `from`'s entries at or below `path` with `b_from`'s.
+fn take_origins(from: &mut Origins, b_from: &Origins, path: &str) {
+    from.retain(|p, _| !under(p, path));
+    let below = b_from.range(path.to_string()..).take_while(|(p, _)| p.starts_with(path));
+    from.extend(below.filter(|(p, _)| under(p, path)).map(|(p, f)| (p.clone(), f.clone())));
+}
+
+/// Record `file` as the origin of every value in `v` (keys without their `!`).
+fn leaf_paths(v: &Yaml, path: &str, file: &str, out: &mut Origins) {
+    match v.as_mapping() {
+        Some(m) if !m.is_empty() => {
+            for (k, sub) in m {
+                let k = k.as_str().and_then(|s| s.strip_suffix('!')).map_or(k.clone(), Yaml::from);
+                leaf_paths(sub, &origin_path(path, &k), file, out);
+            }
+        }
+        _ if !path.is_empty() => {
+            out.insert(path.to_string(), file.to_string());
+        }
+        _ => {}
     }
 }
 
@@ -340,6 +1858,10 @@ fn deep_merge(a: Yaml, b: Yaml) -> Yaml {
 mod tests {
     use super::*;
 
+    fn deep_merge(a: Yaml, b: Yaml) -> Yaml {
+        deep_merge_traced(a, &mut Origins::new(), b, &Origins::new(), "")
+    }
+
     fn ctx(pairs: &[(&str, Value)]) -> Context {
         pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
     }
@@ -348,6 +1870,15 @@ mod tests {
         Ruleset::load("t5", "").unwrap()
     }
 
+    /// t5 with `yaml` merged over it, built but not yet validated.
+    fn t5_with(yaml: &str) -> Result<Ruleset, String> {
+        let data = deep_merge(
+            load_merged("t5", "", &mut Vec::new()).unwrap().0,
+            serde_yaml::from_str(yaml).unwrap(),
+        );
+        Ruleset::build("x".into(), data)
+    }
+
     #[test]
     fn t5_trade_codes() {
         let rs = t5();
@@ -413,6 +1944,325 @@ mod tests {
         assert!(cep.base_meets(8, 8));
     }
 
+    #[test]
+    fn uwp_layout_and_alphabet() {
+        let rs = t5();
+        let earth = ctx(&[
+            ("port", Value::Str("A".into())), ("size", Value::Int(8)), ("atmo", Value::Int(6)),
+            ("hydro", Value::Int(7)), ("pop", Value::Int(9)), ("gov", Value::Int(7)),
+            ("law", Value::Int(5)), ("tech", Value::Int(17)), ("temp", Value::Str("H".into())),
+        ]);
+        assert_eq!(rs.uwp_code(&earth), "A867975-H");
+        assert_eq!(rs.hex_digit(18), 'J');
+        assert_eq!(rs.hex_digit(99), 'Z');
+        let moon = ctx(&[("port", Value::Str("X".into())), ("atmo", Value::Int(13))]);
+        assert_eq!(rs.uwp_code(&moon), "X.D....-.");
+
+        let climate = "uwp_layout: [port, size, atmo, hydro, \"if(temp=='H', 2, 1)\", pop, gov, law, \"-\", tech]";
+        let rs = t5_with(&format!("hex: \"0123456789ABCDEFGHIJ\"\n{climate}")).unwrap();
+        assert_eq!(rs.uwp_code(&earth), "A8672975-H");
+        assert_eq!(rs.hex_digit(18), 'I');
+        assert_eq!(rs.uwp_code(&moon), "X.D.....-.");
+        let short = t5_with("uwp_layout: [port, size, atmo, hydro]").unwrap();
+        assert_eq!(short.uwp_code(&earth), "A867");
+
+        let err = t5_with("uwp_layout: [port, sise]").and_then(|rs| rs.validate()).unwrap_err();
+        assert!(err.contains("uwp_layout.1: undefined name `sise`"), "{err}");
+        assert!(t5_with("uwp_layout: []").and_then(|rs| rs.validate()).unwrap_err().contains("uwp_layout: empty"));
+        // A ruleset without a layout writes the standard one.
+        let bare = Ruleset::build("x".into(), serde_yaml::from_str("hex: \"0123456789ABCDEF\"").unwrap()).unwrap();
+        assert_eq!(bare.uwp_code(&earth), "A867975-F");
+    }
+
+    #[test]
+    fn starport_roll_and_pop_dms() {
+        rng::init_rng("starport-test");
+        let rs = t5();
+        assert!((0..200).all(|_| (2..=12).contains(&rs.starport_roll(&Context::new()))));
+        assert_eq!(rs.starport_pop_dm(9), 0);
+        let mgt = Ruleset::load("mgt2", "").unwrap();
+        assert_eq!(mgt.starport_pop_dm(0), -2);
+        assert_eq!(mgt.starport_pop_dm(4), -1);
+        assert_eq!(mgt.starport_pop_dm(8), 1);
+        assert_eq!(mgt.starport_pop_dm(15), 2);
+
+        let rich = t5_with("starport: { roll: \"2d6 + (size >= 8) * 4\", pop_dm: [0, 1] }").unwrap();
+        let big = ctx(&[("size", Value::Int(9))]);
+        assert!((0..100).all(|_| rich.starport_roll(&big) >= 6));
+        assert_eq!(rich.starport_pop_dm(7), 1);
+
+        let err = t5_with("starport: { method: roll-high }").and_then(|rs| rs.validate()).unwrap_err();
+        assert!(err.contains("starport.method: no longer read"), "{err}");
+        let err = t5_with("starport: { roll: \"2d6 + pop\" }").and_then(|rs| rs.validate()).unwrap_err();
+        assert!(err.contains("starport.roll"), "{err}");
+        let err = t5_with("starport: { pop_dm: { 9: 1 } }").and_then(|rs| rs.validate()).unwrap_err();
+        assert!(err.contains("starport.pop_dm"), "{err}");
+    }
+
+    #[test]
+    fn mgt2_inherits_and_overrides() {
+        rng::init_rng("mgt2-test");
+        let mgt = Ruleset::load("mgt2", "").unwrap();
+        assert_eq!(mgt.title(), "Mongoose Traveller 2e");
+        assert_eq!(mgt.module_for("extensions").unwrap(), "none");
+        assert_eq!(mgt.starport(2), "X");
+        assert_eq!(mgt.starport(12), "A");
+        assert_eq!(mgt.base_threshold("naval", "A"), Some(8));
+        assert_eq!(mgt.base_threshold("scout", "B"), Some(8));
+        assert_eq!(mgt.base_threshold("military", "A"), Some(8));
+        assert_eq!(mgt.base_threshold("military", "C"), Some(10));
+        assert_eq!(mgt.base_code("military"), Some('M'));
+        assert_eq!(mgt.base_threshold("research", "A"), None);
+        assert!(mgt.base_meets(8, 8));
+
+        // No reroll: Size and Population stop at A. Hydrographics follows Atmosphere.
+        for _ in 0..300 {
+            assert!(mgt.uwp_step("size", &Context::new()) <= 10);
+            assert!(mgt.uwp_step("pop", &Context::new()) <= 10);
+        }
+        let thin = ctx(&[("size", Value::Int(8)), ("atmo", Value::Int(1))]);
+        assert!((0..100).all(|_| mgt.uwp_step("hydro", &thin) <= 2));
+        assert!((0..100).all(|_| mgt.uwp_step("hydro", &ctx(&[("size", Value::Int(1))])) == 0));
+
+        let earth = ctx(&[
+            ("size", Value::Int(8)), ("atmo", Value::Int(6)), ("hydro", Value::Int(7)),
+            ("pop", Value::Int(9)), ("gov", Value::Int(7)), ("law", Value::Int(5)),
+            ("port", Value::Str("A".into())), ("tech", Value::Int(12)),
+        ]);
+        assert_eq!(mgt.trade_codes(&earth), ["Ga", "Hi", "Ht"]);
+        assert_eq!(mgt.tech_dm(&earth), 6 + 2 + 2);
+    }
+
+    #[test]
+    fn classic_inherits_and_overrides() {
+        rng::init_rng("classic-test");
+        let ct = Ruleset::load("classic", "").unwrap();
+        assert_eq!(ct.title(), "Classic Traveller");
+        assert_eq!(ct.module_for("extensions").unwrap(), "none");
+        assert_eq!(ct.starport(2), "A");
+        assert_eq!(ct.starport(9), "D");
+        assert_eq!(ct.starport(12), "X");
+        assert_eq!(ct.base_threshold("naval", "A"), Some(8));
+        assert_eq!(ct.base_threshold("scout", "D"), Some(7));
+        assert_eq!(ct.base_threshold("depot", "A"), None);
+        assert!(ct.base_meets(8, 8));
+
+        // Hydrographics follows Size, still DM-4 on a thin atmosphere.
+        let big_thin = ctx(&[("size", Value::Int(10)), ("atmo", Value::Int(1))]);
+        let small_wet = ctx(&[("size", Value::Int(2)), ("atmo", Value::Int(8))]);
+        assert!((0..100).all(|_| ct.uwp_step("hydro", &small_wet) <= 7));
+        assert!((0..100).any(|_| ct.uwp_step("hydro", &big_thin) >= 8));
+
+        let world = ctx(&[
+            ("size", Value::Int(7)), ("atmo", Value::Int(6)), ("hydro", Value::Int(5)),
+            ("pop", Value::Int(6)), ("gov", Value::Int(5)), ("law", Value::Int(4)),
+            ("port", Value::Str("F".into())), ("tech", Value::Int(9)),
+        ]);
+        assert_eq!(ct.trade_codes(&world), ["Ag", "Ni", "Ri"]);
+        assert_eq!(ct.tech_dm(&world), 1);
+    }
+
+    #[test]
+    fn military_formulas() {
+        rng::init_rng("military-test");
+        let rs = t5();
+        assert!(rs.has_military());
+        let c = ctx(&[
+            ("pop", Value::Int(8)), ("tech", Value::Int(12)), ("port", Value::Str("A".into())),
+            ("ix", Value::Int(3)), ("ru", Value::Int(600)), ("naval", Value::Int(1)),
+        ]);
+        assert_eq!(rs.military_step("defence", &c), Some(8));
+        assert_eq!(rs.military_step("budget", &c), Some(540));
+        assert_eq!(rs.military_step("max_ship_tl", &c), Some(12));
+        assert_eq!(rs.military_step("nonesuch", &c), None);
+
+        let cep = Ruleset::load("cepheus", "").unwrap();
+        assert_eq!(cep.military_step("budget", &c), Some(192));
+        assert_eq!(cep.military_step("navy_share", &c), Some(40));
+    }
+
+    #[test]
+    fn base_kinds_from_ruleset() {
+        rng::init_rng("bases-test");
+        let rs = t5();
+        let kinds = rs.base_kinds();
+        for want in ["naval", "scout", "research", "tas", "imperial", "pirate"] {
+            assert!(kinds.iter().any(|k| k == want), "missing {want} in {kinds:?}");
+        }
+        assert_eq!(rs.base_threshold("research", "A"), Some(8));
+        assert_eq!(rs.base_glyph('P').as_deref(), Some("☠"));
+
+        // Pirates need low law and a poor port; a well-policed A port never has one.
+        let safe = ctx(&[("port", Value::Str("A".into())), ("law", Value::Int(9))]);
+        let lawless = ctx(&[
+            ("port", Value::Str("E".into())), ("law", Value::Int(0)), ("gov", Value::Int(0)),
+        ]);
+        let hits = (0..200).filter(|_| rs.base_check("pirate", &lawless)).count();
+        assert!(hits > 20, "pirates rolled only {hits}/200 on a lawless E port");
+        assert!((0..200).all(|_| !rs.base_check("pirate", &safe)));
+    }
+
+    #[test]
+    fn trade_numbers() {
+        let rs = t5();
+        let earth = ctx(&[
+            ("pop", Value::Int(9)), ("tech", Value::Int(12)), ("port", Value::Str("A".into())),
+        ]);
+        assert_eq!(rs.trade_wtn(&earth), 10);
+        let outpost = ctx(&[
+            ("pop", Value::Int(2)), ("tech", Value::Int(4)), ("port", Value::Str("X".into())),
+        ]);
+        assert_eq!(rs.trade_wtn(&outpost), 0);
+        assert_eq!(rs.trade_distance_dm(1), 0);
+        assert_eq!(rs.trade_distance_dm(3), 1);
+        assert_eq!(rs.trade_distance_dm(40), 2);
+        assert!(rs.trade_pairs().contains(&("Ag".to_string(), "Na".to_string())));
+        assert_eq!(rs.trade_route_class(12).as_deref(), Some("major"));
+        assert_eq!(rs.trade_route_class(10).as_deref(), Some("intermediate"));
+        assert_eq!(rs.trade_route_class(2), None);
+    }
+
+    #[test]
+    fn speculation_tables() {
+        let rs = t5();
+        let goods = rs.trade_goods();
+        assert!(goods.len() >= 10);
+        let elec = goods.iter().find(|g| g.name == "Common Electronics").unwrap();
+        assert_eq!(elec.price, 20000);
+        assert!(elec.available_at(&["Ba".to_string()]), "common goods are stocked everywhere");
+        let ore = goods.iter().find(|g| g.name == "Radioactives").unwrap();
+        assert!(!ore.available_at(&["Ga".to_string()]));
+        assert!(ore.available_at(&["As".to_string()]));
+        assert_eq!(rs.speculation_percent("purchase", 8), 100);
+        assert_eq!(rs.speculation_percent("purchase", -99), 300);
+        assert_eq!(rs.speculation_percent("sale", 99), 400);
+
+        let cep = Ruleset::load("cepheus", "").unwrap();
+        assert_ne!(cep.trade_goods(), rs.trade_goods());
+        assert_eq!(cep.speculation_percent("sale", 7), 100);
+    }
+
+    #[test]
+    fn extends_lists_and_mixins() {
+        let dir = std::env::temp_dir().join(format!("astromapper-extends-{}", std::process::id()));
+        fs::create_dir_all(dir.join("rules")).unwrap();
+        let files = [
+            ("no_pirates", "bases: { pirate!: { threshold: {} } }"),
+            ("extra_codes", "trade_codes: { Gw: \"pop>=7 and atmo in 5..8\", Mn: \"size>=10\" }"),
+            (
+                "house",
+                "name: House\nextends: [t5, cepheus]\ninclude: [no_pirates, extra_codes]\n\
+                 trade_codes: { Mn: \"size>=11\" }",
+            ),
+            ("diamond", "extends: [mgt2, classic]"),
+            ("a", "extends: b"),
+            ("b", "extends: t5\ninclude: [c]"),
+            ("c", "extends: a"),
+            ("odd", "extends: { t5: 1 }"),
+        ];
+        for (name, yaml) in files {
+            fs::write(dir.join(format!("rules/{name}.yml")), yaml).unwrap();
+        }
+        let root = dir.to_str().unwrap();
+
+        // Parents in order, then mixins, then the file itself.
+        let rs = Ruleset::load("house", root).unwrap();
+        assert_eq!(rs.title(), "House");
+        assert_eq!(rs.module_for("extensions").unwrap(), "none");
+        assert_eq!(rs.base_threshold("pirate", "X"), None);
+        assert_eq!(rs.base_threshold("naval", "A"), Some(8));
+        let big = ctx(&[("size", Value::Int(10)), ("pop", Value::Int(7)), ("atmo", Value::Int(6))]);
+        assert!(rs.trade_codes(&big).contains(&"Gw".to_string()));
+        assert!(!rs.trade_codes(&big).contains(&"Mn".to_string()));
+        let origin = |path: &str| rs.origins(path).first().map(|(_, f)| f.to_string());
+        assert_eq!(origin("name").as_deref(), Some("house"));
+        assert_eq!(origin("modules.extensions").as_deref(), Some("cepheus"));
+        assert_eq!(origin("modules.climate").as_deref(), Some("t5"));
+        assert_eq!(origin("trade_codes.Gw").as_deref(), Some("extra_codes"));
+        assert_eq!(origin("trade_codes.Mn").as_deref(), Some("house"));
+        // t5 is cepheus's base, so it is applied once, before cepheus's `bases!:`.
+        let kinds: Vec<&str> = rs.origins("bases").iter().map(|(p, _)| p.split('.').nth(1).unwrap()).collect();
+        assert!(kinds.iter().all(|k| ["op", "naval", "scout", "pirate"].contains(k)), "{kinds:?}");
+        assert_eq!(origin("bases.pirate.threshold").as_deref(), Some("no_pirates"));
+        assert_eq!(rs.value_at("starport.table").and_then(|t| t.as_sequence()).map(|t| t.len()), Some(13));
+        assert!(rs.origins("extends").is_empty());
+
+        // Two parents sharing a base is not a cycle; the later parent wins.
+        let rs = Ruleset::load("diamond", root).unwrap();
+        assert_eq!(rs.starport(2), "A");
+        assert_eq!(rs.origins("starport.table"), [("starport.table", "classic")]);
+        assert_eq!(rs.origins("starport.pop_dm"), [("starport.pop_dm", "mgt2")]);
+
+        let err = Ruleset::load("a", root).err().unwrap();
+        assert!(err.contains("cyclic extends/include: a -> b -> c -> a"), "{err}");
+        let err = Ruleset::load("odd", root).err().unwrap();
+        assert!(err.contains("`extends:` wants a ruleset name or a list"), "{err}");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn missing_sections_come_from_t5() {
+        let dir = std::env::temp_dir().join(format!("astromapper-sections-{}", std::process::id()));
+        fs::create_dir_all(dir.join("rules")).unwrap();
+        let mut bare: Yaml = serde_yaml::from_str(builtin("t5").unwrap()).unwrap();
+        for key in T5_SECTIONS {
+            bare.as_mapping_mut().unwrap().remove(key);
+        }
+        fs::write(dir.join("rules/bare.yml"), serde_yaml::to_string(&bare).unwrap()).unwrap();
+
+        let rs = Ruleset::load("bare", dir.to_str().unwrap()).unwrap();
+        for key in T5_SECTIONS {
+            assert_eq!(rs.value_at(key), t5().value_at(key), "{key}");
+            assert!(rs.origins(key).iter().all(|(_, f)| *f == "t5"), "{key}");
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn custom_base_kind_needs_code() {
+        let yaml = "bases: { outpost: { threshold: 8 } }";
+        let rs = Ruleset::build("x".into(), serde_yaml::from_str(yaml).unwrap()).unwrap();
+        assert!(rs.validate().unwrap_err().contains("bases.outpost"));
+    }
+
+    #[test]
+    fn starport_facilities() {
+        rng::init_rng("facilities-test");
+        let rs = t5();
+        let hi = ctx(&[("tech", Value::Int(12))]);
+        let mid = ctx(&[("tech", Value::Int(8))]);
+        let lo = ctx(&[("tech", Value::Int(5))]);
+        assert_eq!(rs.facility_text("A", "fuel", &hi), "refined");
+        assert_eq!(rs.facility_text("A", "shipyard", &hi), "starships");
+        assert_eq!(rs.facility_text("A", "shipyard", &mid), "spacecraft");
+        assert_eq!(rs.facility_text("A", "shipyard", &lo), "none");
+        assert_eq!(rs.facility_text("X", "repair", &hi), "none");
+        assert_eq!(rs.facility_value("A", "highport", &hi), Some(1));
+        assert_eq!(rs.facility_value("A", "highport", &lo), Some(0));
+        assert_eq!(rs.facility_value("E", "berthing", &hi), Some(0));
+        let berth = rs.facility_value("B", "berthing", &hi).unwrap();
+        assert!((500..=3000).contains(&berth), "berthing {berth}");
+        assert_eq!(rs.facility_value("Q", "berthing", &hi), None);
+        assert!(rs.has_facilities("C") && !rs.has_facilities("Q"));
+    }
+
+    #[test]
+    fn culture_tables() {
+        rng::init_rng("culture-test");
+        let rs = t5();
+        assert!(rs.has_culture());
+        let open = ctx(&[("acc", Value::Int(12)), ("homo", Value::Int(9)), ("gov", Value::Int(13))]);
+        assert_eq!(rs.culture_offworlders(&open), "Welcoming");
+        assert_eq!(rs.culture_offworlders(&ctx(&[("acc", Value::Int(1))])), "Xenophobic");
+        let faiths = rs.culture_list("religions", &open);
+        assert_eq!(faiths[0], "State theocracy");
+        assert_eq!(faiths.len(), 2);
+        let split = ctx(&[("homo", Value::Int(1)), ("pop", Value::Int(9))]);
+        let langs = rs.culture_list("languages", &split);
+        assert_eq!(langs.len(), 3);
+        assert!(langs.iter().all(|l| langs.iter().filter(|x| *x == l).count() == 1));
+    }
+
     #[test]
     fn validation_reports_problems() {
         let rs = Ruleset::build("broken".into(), serde_yaml::from_str("{}").unwrap()).unwrap();
@@ -422,5 +2272,147 @@ mod tests {
         }
     }
 
-    use crate::rng;
+    #[test]
+    fn defs_and_tables() {
+        rng::init_rng("tables-test");
+        let rs = t5();
+        assert_eq!(rs.table_entry("gravity", 8), Some(Value::Int(100)));
+        assert_eq!(rs.table_entry("factions", 12), Some(Value::Str("P".into())));
+        assert_eq!(rs.table_entry("nonesuch", 1), None);
+
+        let rs = t5_with(
+            "tables: { sizes: [{ value: 4, weight: 0 }, { value: 7, weight: 5 }] }\n\
+             defs: { heavy: \"table('gravity', size) >= 125\" }\n\
+             uwp: { size: { roll: \"pick('sizes')\", reroll: } }\n\
+             trade_codes: { Hv: heavy }",
+        )
+        .unwrap();
+        assert!(rs.validate().is_ok(), "{:?}", rs.validate());
+        assert!((0..50).all(|_| rs.uwp_step("size", &Context::new()) == 7));
+        assert!(rs.trade_codes(&ctx(&[("size", Value::Int(9))])).contains(&"Hv".to_string()));
+        assert!(!rs.trade_codes(&ctx(&[("size", Value::Int(8))])).contains(&"Hv".to_string()));
+
+        assert!(t5_with("defs: { size: '1' }").err().unwrap().contains("shadows"));
+        assert!(t5_with("uwp: { pop: { roll: \"table('nope', 1)\" } }").err().unwrap().contains("unknown table"));
+        let mixed = t5_with("tables: { m: [1, A] }\nuwp: { pop: { roll: \"table('m', 1)\" } }").unwrap();
+        assert!(mixed.validate().unwrap_err().contains("uwp.pop.roll: table \"m\" mixes"));
+    }
+
+    #[test]
+    fn star_tables() {
+        rng::init_rng("stars-test");
+        let rs = t5();
+        assert_eq!(rs.star_census("opera"), "sunlike");
+        assert_eq!(rs.star_census("firm"), "natural");
+        assert!((0..20).all(|_| ["sunlike", "natural"].contains(&rs.star_census("pulp").as_str())));
+        assert!((0..50).all(|_| {
+            let t = rs.star_roll("types", "hot", &Context::new()).unwrap();
+            ["A", "B", "O"].contains(&t.as_str())
+        }));
+        assert_eq!(rs.star_physical("G", "V"), Some((1.0, 1.0, 5800)));
+        assert_eq!(rs.star_physical("K", "D"), Some((0.8, 0.4 * 0.001, 4500)));
+        assert_eq!(rs.star_value("companions/count", &Context::new()), 0);
+
+        let rs = t5_with(
+            "genres: { firm: { census: giants } }\nstars:\n  types: { giants: { roll: '1', table: [K] } }\n  \
+             classes: { K: { roll: '2', from: 1, table: [V, III] } }\n  companions: { count: '2' }",
+        )
+        .unwrap();
+        assert!(rs.validate().is_ok(), "{:?}", rs.validate());
+        assert_eq!(rs.star_roll("types", &rs.star_census("firm"), &Context::new()).unwrap(), "K");
+        assert_eq!(rs.star_class_table("K"), "K");
+        assert_eq!(rs.star_roll("classes", "K", &Context::new()).unwrap(), "III");
+        assert_eq!(rs.star_value("companions/count", &Context::new()), 2);
+
+        let err = t5_with("genres: { opera: { census: nowhere } }\nstars: { types: { natural: { table: [F, X] } } }")
+            .and_then(|rs| rs.validate())
+            .unwrap_err();
+        assert!(err.contains("genres.opera.census: no table `stars.types.nowhere`"), "{err}");
+        assert!(err.contains("stars.types.natural: \"X\" is neither a spectral type nor a table"), "{err}");
+        let err = t5_with("genres: { normal: { census: \"1d6\" } }").and_then(|rs| rs.validate()).unwrap_err();
+        assert!(err.contains("genres.normal.census: expected a string"), "{err}");
+    }
+
+    #[test]
+    fn genres_from_data() {
+        rng::init_rng("genres-test");
+        let rs = t5();
+        assert_eq!(rs.genres(), ["normal", "opera", "firm"]);
+        let world = ctx(&[("size", Value::Int(2)), ("atmo", Value::Int(6)), ("hydro", Value::Int(7))]);
+        assert_eq!(rs.genre_adjust("normal", "atmo", &world, 6), 6);
+        assert_eq!(rs.genre_adjust("firm", "atmo", &world, 6), 0);
+        let thin = ctx(&[("size", Value::Int(2)), ("atmo", Value::Int(0))]);
+        assert_eq!(rs.genre_adjust("opera", "hydro", &thin, 7), 1);
+        assert_eq!(rs.genre_adjust("opera", "hydro", &thin, 3), 0);
+        assert_eq!(rs.genre_dm("firm", "pop_dm", &world), 0); // small, but breathable
+        assert_eq!(rs.genre_dm("firm", "pop_dm", &thin), -2);
+        assert_eq!(rs.genre_dm("opera", "pop_dm", &world), 0);
+        assert_eq!(rs.genre_dm("firm", "port_dm", &ctx(&[("pop", Value::Int(9))])), -2);
+
+        // A project adding a genre over the ruleset, as `_astromapper.yml` does.
+        let extra = "genres: { hard-sf: { census: natural, pop_dm: '-3', atmo: [{ when: 'size < 5', set: 0 }] } }";
+        let rs = t5().overlay(serde_yaml::from_str(extra).unwrap()).unwrap();
+        assert_eq!(rs.genres(), ["normal", "opera", "firm", "hard-sf"]);
+        assert_eq!(rs.star_census("hard-sf"), "natural");
+        assert_eq!(rs.genre_adjust("hard-sf", "atmo", &world, 6), 0);
+        assert_eq!(rs.genre_dm("hard-sf", "pop_dm", &world), -3);
+
+        let err = t5()
+            .overlay(serde_yaml::from_str("genres: { Pulp: { census: sunlike, pop_dm: 'popp' } }").unwrap())
+            .err()
+            .unwrap();
+        assert!(err.contains("genres.Pulp: genre names are lowercase"), "{err}");
+        assert!(err.contains("genres.Pulp.pop_dm: undefined name `popp`"), "{err}");
+    }
+
+    #[test]
+    fn orbit_tables() {
+        rng::init_rng("orbits-test");
+        let rs = t5();
+        assert!((0..50).all(|_| (-2..=20).contains(&rs.orbit_count("M", "VI"))));
+        assert!((0..50).all(|_| rs.orbit_body("outer", &Context::new()) != "hostile"
+            || rs.orbit_value("zones/outer/hostile/hydro", &Context::new()) == 0));
+        let ring = ctx(&[("size", Value::Int(0)), ("orbit", Value::Int(12))]);
+        assert!((0..50).all(|_| (1..=3).contains(&rs.moon_radius(&ring))));
+        let extreme = ctx(&[("size", Value::Int(2)), ("orbit", Value::Int(12))]);
+        assert!((0..50).all(|_| rs.moon_radius(&extreme) >= 75));
+
+        // A gas-giant-rich setting, as a ruleset extending t5 would write it.
+        let rich = t5_with(
+            "orbits:\n  count_dm: { type: { M: 2 } }\n  \
+             zones: { outer: { roll: '1', from: 1, table: [large_giant] } }",
+        )
+        .unwrap();
+        assert!(rich.validate().is_ok(), "{:?}", rich.validate());
+        assert!((0..20).all(|_| rich.orbit_body("outer", &Context::new()) == "large_giant"));
+        assert!((0..50).all(|_| rich.orbit_count("M", "V") >= 4));
+
+        let err = t5_with("orbits: { zones: { inner: { table: [empty, moon] } } }")
+            .and_then(|rs| rs.validate())
+            .unwrap_err();
+        assert!(err.contains("orbits.zones.inner: unknown body \"moon\""), "{err}");
+    }
+
+    #[test]
+    fn validation_type_checks_expressions() {
+        let typos = r#"
+trade_codes: { Ag: "hydor>=4", Tz: "temp==2" }
+uwp: { size: { roll: "2d6-2+tech" } }
+military: { budget: "fleet * 2" }
+culture: { offworlders: [{ when: "law>=0", text: Anyone }] }
+xboat: { hub: "naval" }
+"#;
+        let err = t5_with(typos).and_then(|rs| rs.validate()).unwrap_err();
+        for want in [
+            "trade_codes.Ag: undefined name `hydor`",
+            "trade_codes.Tz: compares a string with a number",
+            "uwp.size.roll: `tech` is not known yet at this step",
+            "military.budget: `fleet` is not known yet",
+            "culture.offworlders.0: condition is always true",
+            "xboat.hub: a number used as a condition",
+        ] {
+            assert!(err.contains(want), "error {err:?} missing {want:?}");
+        }
+        assert_eq!(err.matches("\n- ").count(), 6, "{err}");
+    }
 }
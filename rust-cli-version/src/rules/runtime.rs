@@ -3,18 +3,23 @@
 //! one thread, mirroring the thread-local RNG.
 
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
-use super::Ruleset;
+use super::modules::{ClimateModule, ExtensionsModule, Module, NativeModule, OrbitModule, StarModule};
+use super::{script, Ruleset};
 
 thread_local! {
     static RULESET: RefCell<Option<Rc<Ruleset>>> = const { RefCell::new(None) };
     static GENRE: RefCell<String> = RefCell::new(String::from("normal"));
     static SOPHONTS: RefCell<String> = RefCell::new(String::from("human"));
+    static MODULES: RefCell<BTreeMap<(String, String), Module>> = const { RefCell::new(BTreeMap::new()) };
 }
 
 pub fn set_ruleset(rs: Ruleset) {
     RULESET.with(|r| *r.borrow_mut() = Some(Rc::new(rs)));
+    // Scripts are loaded relative to the ruleset's project, so drop any cached.
+    MODULES.with(|m| m.borrow_mut().retain(|(_, name), _| !name.starts_with("script:")));
 }
 
 /// The active ruleset, lazily loading the built-in t5 if none was set.
@@ -44,3 +49,78 @@ pub fn set_sophonts(s: &str) {
 pub fn sophonts() -> String {
     SOPHONTS.with(|x| x.borrow().clone())
 }
+
+/// Register a module implementation under `name` for its slot, so a ruleset's
+/// `modules:` can select it (`climate: mine`). Replaces an earlier registration,
+/// and may shadow a built-in. Register before loading the ruleset: validation
+/// rejects names it does not know.
+pub fn register_module(name: &str, module: Module) {
+    let key = (module.slot().to_string(), name.to_lowercase());
+    MODULES.with(|m| m.borrow_mut().insert(key, module));
+}
+
+/// Every name a slot can be set to: the built-ins plus any registered.
+pub fn module_names(slot: &str) -> Vec<String> {
+    let mut names: Vec<String> = Module::builtin_names(slot).into_iter().map(String::from).collect();
+    MODULES.with(|m| {
+        names.extend(m.borrow().keys().filter(|(s, n)| s == slot && !n.starts_with("script:")).map(|(_, n)| n.clone()));
+    });
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The active ruleset's implementation for `slot`: registered first, then built-in.
+/// A `script:` module is loaded on first use and kept until the ruleset changes.
+fn module(slot: &str) -> Result<Module, String> {
+    let rs = ruleset();
+    let name = rs.module_for(slot)?;
+    if let Some(path) = name.strip_prefix("script:") {
+        let key = (slot.to_string(), name.clone());
+        if let Some(m) = MODULES.with(|m| m.borrow().get(&key).cloned()) {
+            return Ok(m);
+        }
+        let m = script::load(slot, path, rs.root())?;
+        MODULES.with(|c| c.borrow_mut().insert(key, m.clone()));
+        return Ok(m);
+    }
+    let registered = MODULES.with(|m| m.borrow().get(&(slot.to_string(), name.clone())).cloned());
+    registered.or_else(|| Module::builtin(slot, &name)).ok_or_else(|| {
+        format!("modules.{slot}: unknown module {name:?} (known: {})", module_names(slot).join(", "))
+    })
+}
+
+pub fn star_module() -> Result<Rc<dyn StarModule>, String> {
+    match module("stars")? {
+        Module::Stars(m) => Ok(m),
+        _ => unreachable!("registered under its own slot"),
+    }
+}
+
+pub fn orbit_module() -> Result<Rc<dyn OrbitModule>, String> {
+    match module("orbits")? {
+        Module::Orbits(m) => Ok(m),
+        _ => unreachable!("registered under its own slot"),
+    }
+}
+
+pub fn climate_module() -> Result<Rc<dyn ClimateModule>, String> {
+    match module("climate")? {
+        Module::Climate(m) => Ok(m),
+        _ => unreachable!("registered under its own slot"),
+    }
+}
+
+pub fn extensions_module() -> Result<Rc<dyn ExtensionsModule>, String> {
+    match module("extensions")? {
+        Module::Extensions(m) => Ok(m),
+        _ => unreachable!("registered under its own slot"),
+    }
+}
+
+pub fn native_module() -> Result<Rc<dyn NativeModule>, String> {
+    match module("native")? {
+        Module::Native(m) => Ok(m),
+        _ => unreachable!("registered under its own slot"),
+    }
+}
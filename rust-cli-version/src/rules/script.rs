@@ -0,0 +1,287 @@
+//! Scripted modules: a ruleset's `modules:` slot may name a Rhai script instead of
+//! a compiled module (`extensions: script:rules/my_ext.rhai`, relative to the
+//! project directory). Needs the `scripting` feature.
+//!
+//! A script defines its slot's entry point, with `this` bound to the object it
+//! works on (read and write; converted through serde, so field names are the JSON
+//! export's):
+//!
+//! | slot | entry point | `this` | returns |
+//! |---|---|---|---|
+//! | stars | `primary(genre)` | a G2V primary to reshape | — |
+//! | orbits | `populate_orbits()` | the star, `orbits` empty | — |
+//! | climate | `climate(orbit)` | the mainworld | `"Hot"`, `"Temperate"`, ... |
+//! | extensions | `build_extensions(gas_giants, belts)` | the mainworld | — |
+//! | native | `native_status()` | the mainworld | a string |
+//!
+//! Dice come from the seeded generator (`roll(dice, sides)`, `d6()`, `flux()`), so
+//! scripted output is as reproducible as built-in output, and `t5_primary(genre)`,
+//! `t5_orbits(star)`, `t5_climate(world, orbit)`, `t5_extensions(world, gg, belts)`
+//! and `t5_native(world)` return what the built-in module would, to adjust rather
+//! than replace it. Scripts cannot `import` files, `eval` or print, and are stopped
+//! after a fixed number of operations or on building an oversized string, array
+//! or map.
+
+#[cfg(not(feature = "scripting"))]
+use super::modules::Module;
+
+/// Each slot's entry point and its argument count.
+pub fn entry_point(slot: &str) -> Option<(&'static str, usize)> {
+    Some(match slot {
+        "stars" => ("primary", 1),
+        "orbits" => ("populate_orbits", 0),
+        "climate" => ("climate", 1),
+        "extensions" => ("build_extensions", 2),
+        "native" => ("native_status", 0),
+        _ => return None,
+    })
+}
+
+/// Load the script at `path` (under `root`) as the module for `slot`, checking it
+/// compiles and defines the slot's entry point.
+#[cfg(not(feature = "scripting"))]
+pub fn load(slot: &str, path: &str, _root: &str) -> Result<Module, String> {
+    Err(format!(
+        "modules.{slot}: `script:{path}` needs astromapper built with `--features scripting`"
+    ))
+}
+
+#[cfg(feature = "scripting")]
+pub use engine::load;
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::fs;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use rhai::module_resolvers::DummyModuleResolver;
+    use rhai::serde::{from_dynamic, to_dynamic};
+    use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Scope, AST};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use super::entry_point;
+    use crate::error::{AstromapperError, Result};
+    use crate::models::world::{Temperature, World};
+    use crate::models::{Star, StarSize, StarType};
+    use crate::rng;
+    use crate::rules::modules::{
+        ClimateModule, ExtensionsModule, Module, NativeModule, OrbitModule, StarModule, T5Module,
+    };
+
+    /// Operations a single call may run before it is stopped (runaway loops).
+    const MAX_OPERATIONS: u64 = 1_000_000;
+    /// Largest string, array and object map a script may build (runaway memory);
+    /// a star or world converts to well under these.
+    const MAX_STRING: usize = 64 * 1024;
+    const MAX_ARRAY: usize = 10_000;
+    const MAX_MAP: usize = 1_000;
+
+    pub struct ScriptModule {
+        path: String,
+        engine: Engine,
+        ast: AST,
+    }
+
+    pub fn load(slot: &str, path: &str, root: &str) -> std::result::Result<Module, String> {
+        let (name, arity) = entry_point(slot).ok_or_else(|| format!("modules.{slot}: not a slot"))?;
+        let file = Path::new(root).join(path);
+        let src = fs::read_to_string(&file).map_err(|e| format!("modules.{slot}: {}: {e}", file.display()))?;
+        let engine = sandbox();
+        let ast = engine.compile(&src).map_err(|e| format!("modules.{slot}: {path}: {e}"))?;
+        if !ast.iter_functions().any(|f| f.name == name && f.params.len() == arity) {
+            return Err(format!("modules.{slot}: {path} defines no `fn {name}` taking {arity} argument(s)"));
+        }
+        let script = Rc::new(ScriptModule { path: path.to_string(), engine, ast });
+        Ok(match slot {
+            "stars" => Module::Stars(script),
+            "orbits" => Module::Orbits(script),
+            "climate" => Module::Climate(script),
+            "extensions" => Module::Extensions(script),
+            _ => Module::Native(script),
+        })
+    }
+
+    type Fallible = std::result::Result<Dynamic, Box<EvalAltResult>>;
+
+    /// A Rhai engine with no file access or console output, bounded time and
+    /// memory, the seeded dice, and the built-in modules.
+    fn sandbox() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(MAX_STRING);
+        engine.set_max_array_size(MAX_ARRAY);
+        engine.set_max_map_size(MAX_MAP);
+        engine.register_fn("roll", |dice: i64, sides: i64| {
+            rng::roll(dice.clamp(0, 100) as u32, sides.clamp(1, 1000) as u32).unwrap_or(0) as i64
+        });
+        engine.register_fn("d6", || rng::roll_1d6() as i64);
+        engine.register_fn("flux", || rng::roll_1d6() as i64 - rng::roll_1d6() as i64);
+        engine.register_fn("t5_primary", |genre: &str| -> Fallible {
+            script_value(&T5Module.primary(genre).map_err(eval_err)?)
+        });
+        engine.register_fn("t5_orbits", |star: Dynamic| -> Fallible {
+            let mut star: Star = rust_value(&star)?;
+            T5Module.populate_orbits(&mut star).map_err(eval_err)?;
+            script_value(&star)
+        });
+        engine.register_fn("t5_climate", |world: Dynamic, orbit: i64| -> Fallible {
+            let world: World = rust_value(&world)?;
+            let orbit = orbit.clamp(0, u8::MAX as i64) as u8;
+            script_value(&T5Module.climate(&world, orbit).map_err(eval_err)?)
+        });
+        engine.register_fn("t5_extensions", |world: Dynamic, gas_giants: i64, belts: i64| -> Fallible {
+            let mut world: World = rust_value(&world)?;
+            T5Module.build_extensions(&mut world, gas_giants, belts).map_err(eval_err)?;
+            script_value(&world)
+        });
+        engine.register_fn("t5_native", |world: Dynamic| -> Fallible {
+            let world: World = rust_value(&world)?;
+            Ok(T5Module.native_status(&world).map_err(eval_err)?.into())
+        });
+        engine
+    }
+
+    fn eval_err(e: impl ToString) -> Box<EvalAltResult> {
+        e.to_string().into()
+    }
+
+    fn script_value<T: Serialize>(v: &T) -> Fallible {
+        to_dynamic(v)
+    }
+
+    fn rust_value<T: DeserializeOwned>(v: &Dynamic) -> std::result::Result<T, Box<EvalAltResult>> {
+        from_dynamic(v)
+    }
+
+    impl ScriptModule {
+        /// Call the script's `name` with `this` bound; errors name the script.
+        fn call(&self, name: &str, this: &mut Dynamic, args: impl FuncArgs) -> Result<Dynamic> {
+            let options = CallFnOptions::new().bind_this_ptr(this);
+            self.engine
+                .call_fn_with_options(options, &mut Scope::new(), &self.ast, name, args)
+                .map_err(|e| self.error(name, e))
+        }
+
+        /// Run `name` over a copy of `value` and read the (possibly changed) copy back.
+        fn update<T: Serialize + DeserializeOwned>(
+            &self,
+            name: &str,
+            value: &mut T,
+            args: impl FuncArgs,
+        ) -> Result<Dynamic> {
+            let mut this = script_value(value).map_err(|e| self.error(name, e))?;
+            let out = self.call(name, &mut this, args)?;
+            *value = rust_value(&this).map_err(|e| self.error(name, e))?;
+            Ok(out)
+        }
+
+        fn error(&self, name: &str, e: impl ToString) -> AstromapperError {
+            AstromapperError::RulesetError(format!("{} `{name}`: {}", self.path, e.to_string()))
+        }
+    }
+
+    impl StarModule for ScriptModule {
+        fn primary(&self, genre: &str) -> Result<Star> {
+            let mut star = Star::new(StarType::G, StarSize::V, true);
+            star.spectral = "G2".to_string();
+            self.update("primary", &mut star, (genre.to_string(),)).map(|_| star)
+        }
+    }
+
+    impl OrbitModule for ScriptModule {
+        fn populate_orbits(&self, star: &mut Star) -> Result<()> {
+            self.update("populate_orbits", star, ()).map(|_| ())
+        }
+    }
+
+    impl ClimateModule for ScriptModule {
+        fn climate(&self, world: &World, orbit_number: u8) -> Result<Temperature> {
+            let mut world = world.clone();
+            let out = self.update("climate", &mut world, (orbit_number as i64,))?;
+            rust_value(&out).map_err(|e| self.error("climate", e))
+        }
+    }
+
+    impl ExtensionsModule for ScriptModule {
+        fn build_extensions(&self, world: &mut World, gas_giants: i64, belts: i64) -> Result<()> {
+            self.update("build_extensions", world, (gas_giants, belts)).map(|_| ())
+        }
+    }
+
+    impl NativeModule for ScriptModule {
+        fn native_status(&self, world: &World) -> Result<String> {
+            let mut world = world.clone();
+            let out = self.update("native_status", &mut world, ())?;
+            out.into_string().map_err(|t| self.error("native_status", format!("returned a {t}, not a string")))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::models::world::World;
+
+        fn script(slot: &str, src: &str) -> std::result::Result<Module, String> {
+            let dir = std::env::temp_dir().join(format!("astromapper-script-{slot}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("s.rhai"), src).unwrap();
+            let m = load(slot, "s.rhai", dir.to_str().unwrap());
+            fs::remove_dir_all(&dir).ok();
+            m
+        }
+
+        #[test]
+        fn scripts_read_and_write_the_world() {
+            rng::init_rng("script-test");
+            let src = "fn build_extensions(gg, belts) { this.ix = gg + belts + d6() * 0; this.extended = true; }";
+            let Module::Extensions(ext) = script("extensions", src).unwrap() else { panic!() };
+            let mut w = World::new(0, 0);
+            ext.build_extensions(&mut w, 2, 1).unwrap();
+            assert_eq!(w.ix, 3);
+            assert!(w.extended);
+
+            let src = "fn climate(orbit) { if orbit < 3 && this.size > 0 { \"Hot\" } else { \"Cold\" } }";
+            let Module::Climate(c) = script("climate", src).unwrap() else { panic!() };
+            w.size = 5;
+            assert_eq!(c.climate(&w, 2).unwrap(), Temperature::Hot);
+            assert_eq!(c.climate(&w, 6).unwrap(), Temperature::Cold);
+        }
+
+        #[test]
+        fn scripts_are_checked_and_sandboxed() {
+            let err = script("native", "fn native(x) { \"\" }").err().unwrap();
+            assert!(err.contains("defines no `fn native_status` taking 0"), "{err}");
+            assert!(script("native", "fn native_status() {").is_err());
+
+            let Module::Native(n) = script("native", "fn native_status() { loop {} }").unwrap() else { panic!() };
+            assert!(n.native_status(&World::new(0, 0)).is_err());
+            let Module::Native(n) = script("native", "fn native_status() { import \"x\" as x; \"\" }").unwrap()
+            else {
+                panic!()
+            };
+            assert!(n.native_status(&World::new(0, 0)).is_err());
+
+            // Doubling a string outgrows the size limit long before the operation limit.
+            let src = "fn native_status() { let s = \"x\"; loop { s += s; } }";
+            let Module::Native(n) = script("native", src).unwrap() else { panic!() };
+            let err = n.native_status(&World::new(0, 0)).unwrap_err().to_string();
+            assert!(err.contains("ength of string"), "{err}");
+            let src = "fn native_status() { let a = [0]; loop { a += a; } }";
+            let Module::Native(n) = script("native", src).unwrap() else { panic!() };
+            assert!(n.native_status(&World::new(0, 0)).is_err());
+            let Module::Native(n) = script("native", "fn native_status() { print(\"hi\"); \"ok\" }").unwrap()
+            else {
+                panic!()
+            };
+            assert_eq!(n.native_status(&World::new(0, 0)).unwrap(), "ok");
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Speculative trade between two worlds: what the source stocks, what a broker pays
+//! for it there and gets for it at the destination. Reads `trade_goods:` and
+//! `speculation:` from the active ruleset.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::World;
+use crate::rng;
+use crate::rules::{runtime, TradeGood};
+
+/// One good on offer at the source, priced at both ends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoodsQuote {
+    pub name: String,
+    pub tons: i64,
+    pub purchase_dm: i64,
+    pub sale_dm: i64,
+    pub purchase_price: i64, // Cr per ton at the source
+    pub sale_price: i64,     // Cr per ton at the destination
+}
+
+impl GoodsQuote {
+    pub fn profit_per_ton(&self) -> i64 {
+        self.sale_price - self.purchase_price
+    }
+}
+
+/// Best DM a world's trade codes earn from one side of a good's DM table.
+fn best_dm(dms: &[(String, i64)], codes: &[String]) -> i64 {
+    dms.iter()
+        .filter(|(code, _)| codes.contains(code))
+        .map(|(_, dm)| *dm)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Purchase DM at the source: its best purchase DM less its best sale DM.
+pub fn purchase_dm(good: &TradeGood, src: &World) -> i64 {
+    best_dm(&good.purchase, &src.trade_codes) - best_dm(&good.sale, &src.trade_codes)
+}
+
+/// Sale DM at the destination: its best sale DM less its best purchase DM.
+pub fn sale_dm(good: &TradeGood, dst: &World) -> i64 {
+    best_dm(&good.sale, &dst.trade_codes) - best_dm(&good.purchase, &dst.trade_codes)
+}
+
+/// Quote every good the source stocks for a run from `from` to `to` (hexes, used with
+/// `seed` to key the dice so the same run always quotes the same prices). The dice
+/// come from a generator of their own; the generation stream is left as it was.
+pub fn speculate(
+    src: &World,
+    dst: &World,
+    from: &str,
+    to: &str,
+    broker: i64,
+    seed: &str,
+) -> Vec<GoodsQuote> {
+    let dice = rng::keyed_rng(&format!("{seed}:{from}:{to}"));
+    rng::with_rng(dice, || quote(src, dst, broker))
+}
+
+fn quote(src: &World, dst: &World, broker: i64) -> Vec<GoodsQuote> {
+    let rs = runtime::ruleset();
+    let ctx = src.context();
+    let mut quotes = Vec::new();
+    for (i, good) in rs.trade_goods().iter().enumerate() {
+        if !good.available_at(&src.trade_codes) {
+            continue;
+        }
+        let tons = rs.trade_good_tons(i, &ctx);
+        let pdm = purchase_dm(good, src);
+        let sdm = sale_dm(good, dst);
+        let buy = rs.speculation_percent("purchase", rs.speculation_roll() + broker + pdm);
+        let sell = rs.speculation_percent("sale", rs.speculation_roll() + broker + sdm);
+        quotes.push(GoodsQuote {
+            name: good.name.clone(),
+            tons,
+            purchase_dm: pdm,
+            sale_dm: sdm,
+            purchase_price: good.price * buy / 100,
+            sale_price: good.price * sell / 100,
+        });
+    }
+    quotes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Ruleset;
+
+    fn world(codes: &[&str]) -> World {
+        let mut w = World::new(0, 0);
+        w.population = 7;
+        w.trade_codes = codes.iter().map(|c| c.to_string()).collect();
+        w
+    }
+
+    #[test]
+    fn dms_and_reproducible_quotes() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let rs = runtime::ruleset();
+        let rad = rs.trade_goods().iter().find(|g| g.name == "Radioactives").unwrap();
+        let asteroid = world(&["As", "Ni"]);
+        let industrial = world(&["In", "Ht"]);
+        assert_eq!(purchase_dm(rad, &asteroid), 4, "As purchase DM less Ni sale DM");
+        assert_eq!(sale_dm(rad, &industrial), 3);
+        assert_eq!(sale_dm(rad, &asteroid), -4, "Ni sale DM less As purchase DM");
+
+        rng::init_rng("goods-test");
+        let untouched = rng::roll_2d6().unwrap();
+        rng::init_rng("goods-test");
+        let a = speculate(&asteroid, &industrial, "0101", "0203", 1, "SEED");
+        let b = speculate(&asteroid, &industrial, "0101", "0203", 1, "SEED");
+        assert_eq!(a, b);
+        assert_eq!(rng::roll_2d6().unwrap(), untouched, "quoting left the generation stream alone");
+        assert!(a.iter().any(|q| q.name == "Radioactives"));
+        assert!(!a.iter().any(|q| q.name == "Live Animals"), "As world stocks no livestock");
+        assert!(a.iter().all(|q| q.tons > 0 && q.purchase_price > 0));
+    }
+}
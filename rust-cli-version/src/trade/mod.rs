@@ -0,0 +1,13 @@
+//! Trade analysis over a generated sector — World and Bilateral Trade Numbers, the
+//! trade routes classed from them, speculative cargo and passenger/freight/mail
+//! traffic between two worlds. Reads the `trade:`, `trade_goods:`, `speculation:`
+//! and `traffic:` sections of the active ruleset; call after the sector is built
+//! (and pruned).
+
+pub mod goods;
+pub mod routes;
+pub mod traffic;
+
+pub use goods::{speculate, GoodsQuote};
+pub use routes::{bilateral_trade_number, trade_routes, TradeRoute};
+pub use traffic::{check_date, traffic, FreightLot, Traffic};
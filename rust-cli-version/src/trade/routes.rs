@@ -0,0 +1,114 @@
+//! Bilateral Trade Numbers between every pair of worlds within the trade jump, and
+//! the major/intermediate/minor routes the ruleset's `trade.routes` classes them into.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::sector::hex_jump;
+use crate::models::{Sector, World};
+use crate::rules::runtime;
+
+/// One classed trade route between two hexes ("CCRR"), `from` < `to`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeRoute {
+    pub from: String,
+    pub to: String,
+    pub jump: i64,
+    pub btn: i64,
+    pub class: String,
+}
+
+/// BTN = WTN + WTN + complementary-code bonuses − the distance DM, capped at the
+/// smaller WTN + `trade.cap`.
+pub fn bilateral_trade_number(a: &World, b: &World, jump: i64) -> i64 {
+    let rs = runtime::ruleset();
+    let has = |w: &World, c: &str| w.trade_codes.iter().any(|x| x == c);
+    let mut btn = a.wtn + b.wtn;
+    for (x, y) in rs.trade_pairs() {
+        if (has(a, &x) && has(b, &y)) || (has(b, &x) && has(a, &y)) {
+            btn += 1;
+        }
+    }
+    btn -= rs.trade_distance_dm(jump);
+    if let Some(cap) = rs.trade_cap() {
+        btn = btn.min(a.wtn.min(b.wtn) + cap);
+    }
+    btn.max(0)
+}
+
+/// Every populated pair within `max_jump` whose BTN reaches a route class, ordered
+/// by hex. Unpopulated worlds generate no trade.
+pub fn trade_routes(sector: &Sector, max_jump: i64) -> Vec<TradeRoute> {
+    let rs = runtime::ruleset();
+    let worlds: Vec<_> = sector
+        .worlds()
+        .into_iter()
+        .filter(|(_, _, w)| w.population > 0)
+        .collect();
+    let mut routes = Vec::new();
+    for (i, &(c1, r1, a)) in worlds.iter().enumerate() {
+        for &(c2, r2, b) in &worlds[i + 1..] {
+            let jump = hex_jump(c1, r1, c2, r2);
+            if jump > max_jump {
+                continue;
+            }
+            let btn = bilateral_trade_number(a, b, jump);
+            if let Some(class) = rs.trade_route_class(btn) {
+                routes.push(TradeRoute {
+                    from: format!("{c1:02}{r1:02}"),
+                    to: format!("{c2:02}{r2:02}"),
+                    jump,
+                    btn,
+                    class,
+                });
+            }
+        }
+    }
+    routes.sort_by(|x, y| (&x.from, &x.to).cmp(&(&y.from, &y.to)));
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Volume;
+    use crate::rules::Ruleset;
+
+    fn world(pop: u8, wtn: i64, codes: &[&str]) -> World {
+        let mut w = World::new(0, 0);
+        w.population = pop;
+        w.wtn = wtn;
+        w.trade_codes = codes.iter().map(|c| c.to_string()).collect();
+        w
+    }
+
+    #[test]
+    fn btn_bonuses_distance_and_cap() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let ag = world(6, 3, &["Ag"]);
+        let na = world(6, 4, &["Na"]);
+        assert_eq!(bilateral_trade_number(&ag, &na, 1), 8);
+        assert_eq!(bilateral_trade_number(&ag, &na, 3), 7);
+        let big = world(9, 9, &[]);
+        let tiny = world(1, 1, &[]);
+        assert_eq!(bilateral_trade_number(&big, &tiny, 1), 6, "capped at min WTN + 5");
+    }
+
+    #[test]
+    fn routes_respect_jump_and_population() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let mut s = Sector::new("t".into(), 32, 40);
+        let mut put = |c: usize, r: usize, w: World| {
+            let mut v = Volume::new(r - 1, c - 1);
+            v.world = Some(w);
+            s.set_volume(r - 1, c - 1, v);
+        };
+        put(1, 1, world(8, 7, &["Ag"]));
+        put(2, 1, world(8, 7, &["Na"]));
+        put(3, 1, world(0, 7, &[])); // barren: no trade
+        put(20, 20, world(8, 7, &[])); // out of range
+        let routes = trade_routes(&s, 4);
+        assert_eq!(routes.len(), 1);
+        assert_eq!((routes[0].from.as_str(), routes[0].to.as_str()), ("0101", "0201"));
+        assert_eq!(routes[0].class, "major");
+    }
+}
@@ -0,0 +1,157 @@
+//! Passenger, freight and mail traffic available for one jump between two worlds.
+//! Reads the `traffic:` section of the active ruleset; the dice are keyed by seed,
+//! route and in-game date so a given week's traffic is always the same.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::World;
+use crate::rng;
+use crate::rules::expr::{Context, Value};
+use crate::rules::runtime;
+
+/// One freight lot on offer: its class (major/minor/incidental) and size.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreightLot {
+    pub class: String,
+    pub tons: i64,
+}
+
+/// Everything waiting at the source for the destination.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Traffic {
+    pub jump: i64,
+    pub passengers: Vec<(String, i64)>, // (class, count) in ruleset order
+    pub freight: Vec<FreightLot>,
+    pub mail: i64, // 5-ton containers
+}
+
+impl Traffic {
+    pub fn freight_tons(&self) -> i64 {
+        self.freight.iter().map(|l| l.tons).sum()
+    }
+}
+
+fn world_ctx(w: &World) -> Context {
+    let mut ctx = w.context();
+    ctx.insert("zone".into(), Value::Str(w.travel_zone().to_string()));
+    ctx
+}
+
+fn roll_dice(n: i64) -> i64 {
+    (0..n).map(|_| rng::roll_1d6() as i64).sum()
+}
+
+/// Check an in-game date is `DDD-YYYY` with a day of 001-365.
+pub fn check_date(date: &str) -> Result<(), String> {
+    let ok = date.split_once('-').is_some_and(|(day, year)| {
+        day.len() == 3
+            && year.len() == 4
+            && day.bytes().chain(year.bytes()).all(|b| b.is_ascii_digit())
+            && (1..=365).contains(&day.parse::<u32>().unwrap_or(0))
+    });
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("bad date {date:?} (want DDD-YYYY, day 001-365)"))
+    }
+}
+
+/// Roll the traffic from `src` to `dst`, `jump` parsecs apart. The `route` hexes,
+/// `seed` and `date` (e.g. "001-1105") key the dice, which come from a generator of
+/// their own; the generation stream is left as it was.
+pub fn traffic(
+    src: &World,
+    dst: &World,
+    jump: i64,
+    route: (&str, &str),
+    seed: &str,
+    date: &str,
+) -> Traffic {
+    let dice = rng::keyed_rng(&format!("{seed}:{}:{}:{date}", route.0, route.1));
+    rng::with_rng(dice, || roll_traffic(src, dst, jump))
+}
+
+fn roll_traffic(src: &World, dst: &World, jump: i64) -> Traffic {
+    let rs = runtime::ruleset();
+    let mut out = Traffic { jump, ..Traffic::default() };
+    if !rs.has_traffic() || src.population == 0 || dst.population == 0 {
+        return out;
+    }
+    let (sctx, dctx) = (world_ctx(src), world_ctx(dst));
+    let mut jctx = Context::new();
+    jctx.insert("jump".into(), Value::Int(jump));
+    let distance = rs.traffic_step("distance", &jctx);
+    let passage = rs.traffic_step("world", &sctx) + rs.traffic_step("world", &dctx) + distance;
+    let freight =
+        rs.traffic_step("freight_world", &sctx) + rs.traffic_step("freight_world", &dctx) + distance;
+
+    for (class, dm) in rs.traffic_classes("passengers") {
+        let dice = rs.traffic_dice(rs.traffic_step("roll", &jctx) + passage + dm);
+        out.passengers.push((class, roll_dice(dice)));
+    }
+    for (class, dm) in rs.traffic_classes("freight") {
+        let lots = roll_dice(rs.traffic_dice(rs.traffic_step("roll", &jctx) + freight + dm));
+        for _ in 0..lots {
+            let tons = rs.traffic_step(&format!("lots/{class}"), &sctx).max(1);
+            out.freight.push(FreightLot { class: class.clone(), tons });
+        }
+    }
+    let mut mctx = sctx;
+    mctx.insert("freight".into(), Value::Int(freight));
+    out.mail = rs.traffic_step("mail", &mctx).max(0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Ruleset;
+
+    fn world(pop: u8, port: char, tech: u8) -> World {
+        let mut w = World::new(0, 0);
+        w.population = pop;
+        w.starport = port;
+        w.tech_level = tech;
+        w.government = 4;
+        w.law_level = 4;
+        w
+    }
+
+    #[test]
+    fn traffic_scales_and_reproduces() {
+        runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+        let rs = runtime::ruleset();
+        assert_eq!(rs.traffic_dice(1), 0);
+        assert_eq!(rs.traffic_dice(7), 3);
+        assert_eq!(rs.traffic_dice(99), 10);
+        assert_eq!(rs.traffic_step("world", &world_ctx(&world(9, 'A', 12))), 5);
+
+        let (hub, other) = (world(9, 'A', 12), world(8, 'B', 10));
+        rng::init_rng("traffic-test");
+        let untouched = rng::roll_2d6().unwrap();
+        rng::init_rng("traffic-test");
+        let a = traffic(&hub, &other, 1, ("0101", "0102"), "S", "001-1105");
+        assert_eq!(a, traffic(&hub, &other, 1, ("0101", "0102"), "S", "001-1105"));
+        assert_eq!(rng::roll_2d6().unwrap(), untouched, "traffic left the generation stream alone");
+        assert_ne!(a, traffic(&hub, &other, 1, ("0101", "0102"), "S", "008-1105"));
+        let classes: Vec<_> = a.passengers.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(classes, ["high", "middle", "low"]);
+        assert!(a.passengers.iter().all(|&(_, n)| n > 0), "busy route always has passengers");
+        assert!(a.freight_tons() > 0);
+
+        let (dump, rock) = (world(1, 'X', 2), world(1, 'E', 3));
+        let b = traffic(&dump, &rock, 6, ("0101", "0107"), "S", "001-1105");
+        assert!(b.passengers.iter().all(|&(_, n)| n == 0));
+        assert!(b.freight.is_empty());
+        assert_eq!(b.mail, 0);
+    }
+
+    #[test]
+    fn dates() {
+        assert!(check_date("001-1105").is_ok());
+        assert!(check_date("365-1105").is_ok());
+        for bad in ["", "1105", "000-1105", "366-1105", "1-1105", "001-105", "abc-1105", "001-1105x"] {
+            assert!(check_date(bad).is_err(), "{bad:?}");
+        }
+    }
+}
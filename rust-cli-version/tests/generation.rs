@@ -1,10 +1,46 @@
 //! Integration tests for ruleset-driven generation: the genre stellar model and
-//! cepheus divergence, mirroring the Ruby/Go suites.
+//! cepheus divergence, mirroring the Ruby/Go suites, and modules registered by a
+//! program built on the library.
+
+use std::path::PathBuf;
+use std::rc::Rc;
 
 use astromapper_core::builders::StarBuilder;
-use astromapper_core::models::StarType;
+use astromapper_core::error::Result;
+use astromapper_core::models::world::{Base, Temperature, World};
+use astromapper_core::models::{OrbitContent, StarType};
 use astromapper_core::rng;
-use astromapper_core::rules::{runtime, Ruleset};
+use astromapper_core::rules::{runtime, ClimateModule, Module, Ruleset};
+
+/// A throwaway project directory holding `rules/<file>` for each entry; removed
+/// when dropped.
+struct Project(PathBuf);
+
+impl Project {
+    fn new(tag: &str, rules: &[(&str, &str)]) -> Project {
+        let dir = std::env::temp_dir().join(format!("astromapper-{tag}-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("rules")).unwrap();
+        let project = Project(dir);
+        for (file, text) in rules {
+            project.write(file, text);
+        }
+        project
+    }
+
+    fn write(&self, file: &str, text: &str) {
+        std::fs::write(self.0.join("rules").join(file), text).unwrap();
+    }
+
+    fn root(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
 
 fn census(genre: &str) -> (f64, f64) {
     runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
@@ -40,3 +76,152 @@ fn genre_stellar_model() {
     );
     assert!(firm_m > 0.5, "firm should be M-dwarf-heavy, got {firm_m:.2}");
 }
+
+struct Frozen;
+
+impl ClimateModule for Frozen {
+    fn climate(&self, _world: &World, _orbit_number: u8) -> Result<Temperature> {
+        Ok(Temperature::Cold)
+    }
+}
+
+#[test]
+fn registered_modules() {
+    let project = Project::new(
+        "modules",
+        &[
+            ("iceball.yml", "extends: t5\nmodules: { climate: frozen }\n"),
+            ("typo.yml", "extends: t5\nmodules: { native: tt5 }\n"),
+        ],
+    );
+    let root = project.root();
+
+    // Unknown names are a load error, not a silent fallback to t5.
+    let err = Ruleset::load("iceball", root).err().unwrap();
+    assert!(err.contains("modules.climate: unknown module \"frozen\" (known: none, t5)"), "{err}");
+    let err = Ruleset::load("typo", root).err().unwrap();
+    assert!(err.contains("modules.native: unknown module \"tt5\""), "{err}");
+
+    runtime::register_module("frozen", Module::Climate(Rc::new(Frozen)));
+    assert_eq!(runtime::module_names("climate"), ["frozen", "none", "t5"]);
+    runtime::set_ruleset(Ruleset::load("iceball", root).unwrap());
+    runtime::set_genre("normal");
+    rng::init_rng("modules");
+    let mut worlds = 0;
+    for _ in 0..50 {
+        let star = StarBuilder::build_primary().unwrap();
+        for o in &star.orbits {
+            if let OrbitContent::World(w) = o {
+                assert_eq!(w.world.temperature, Temperature::Cold);
+                worlds += 1;
+            }
+        }
+    }
+    assert!(worlds > 0);
+}
+
+#[test]
+fn script_modules() {
+    // Orbit worlds are small colonies, so the cut-off is low enough to be hit.
+    let native = "fn native_status() {\n  if this.population >= 5 { \"Teeming\" } else { t5_native(this) }\n}\n";
+    let project = Project::new(
+        "scripts",
+        &[
+            ("scripted.yml", "extends: t5\nmodules:\n  orbits: script:rules/orbits.rhai\n  native: script:rules/native.rhai\n"),
+            ("escape.yml", "extends: t5\nmodules: { native: script:../native.rhai }\n"),
+            ("orbits.rhai", "fn populate_orbits() { this = t5_orbits(this); }\n"),
+            ("native.rhai", native),
+        ],
+    );
+    let root = project.root();
+
+    let err = Ruleset::load("escape", root).err().unwrap();
+    assert!(err.contains("modules.native: bad script path \"../native.rhai\""), "{err}");
+    if cfg!(not(feature = "scripting")) {
+        let err = Ruleset::load("scripted", root).err().unwrap();
+        assert!(err.contains("needs astromapper built with `--features scripting`"), "{err}");
+        return;
+    }
+
+    let systems = |rules: &str| {
+        runtime::set_ruleset(Ruleset::load(rules, root).unwrap());
+        runtime::set_genre("normal");
+        rng::init_rng("scripts");
+        (0..40).map(|_| StarBuilder::build_primary().unwrap()).collect::<Vec<_>>()
+    };
+    let natives = |stars: &[astromapper_core::models::Star]| -> Vec<String> {
+        stars
+            .iter()
+            .flat_map(|s| &s.orbits)
+            .filter_map(|o| match o {
+                OrbitContent::World(w) => Some(w.world.native.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    // A script that defers to t5 changes nothing else; dice stay on the seed.
+    let (plain, scripted) = (systems("t5"), systems("scripted"));
+    assert_eq!(plain.len(), scripted.len());
+    let (plain, scripted) = (natives(&plain), natives(&scripted));
+    assert_eq!(plain.len(), scripted.len());
+    assert!(scripted.iter().any(|n| n == "Teeming"));
+    for (p, s) in plain.iter().zip(&scripted) {
+        assert!(p == s || s == "Teeming", "{p} / {s}");
+    }
+}
+
+/// Each system as its hex, name, UWP and trade codes: the `.tab` row without the
+/// columns optional extras may add to.
+fn sector_rows(rules: &str, root: &str) -> Vec<String> {
+    runtime::set_ruleset(Ruleset::load(rules, root).unwrap());
+    runtime::set_genre("normal");
+    let sector = astromapper_core::generate_sector("Extras".into(), "EXTRAS".into(), 0.33).unwrap();
+    sector
+        .to_tab("Na")
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .skip(1)
+        .map(|l| {
+            let cols: Vec<&str> = l.split('\t').collect();
+            [cols[2], cols[3], cols[4], cols[6]].join(" ")
+        })
+        .collect()
+}
+
+#[test]
+fn optional_extras_keep_the_seed() {
+    let project = Project::new("extras", &[]);
+    let root = project.root();
+    let plain = sector_rows("t5", root);
+    assert!(plain.len() > 100);
+
+    // Editing an optional section rolls different dice for it, but only on the
+    // hex's own stream: every system keeps the UWP the seed gave it.
+    let variants = [
+        ("military", "military: { squadrons: \"3d6 + 2d6\", fleet: \"pop * 2d6 * 10\" }"),
+        ("culture", "culture: { religions: { count: \"1d6\", table: [Sun Cult, Ancestors, None] } }"),
+        ("bases", "bases: { research: { threshold: { A: 6, B: 8 } }, outpost: { threshold: { C: 9 }, op: \">=\", code: O } }"),
+        ("facilities", "starport: { facilities: { A: { berthing: \"3d6 * 1000\" }, B: { highport: \"2d6>=7\" } } }"),
+    ];
+    for (name, yaml) in variants {
+        project.write(&format!("{name}.yml"), &format!("extends: t5\n{yaml}\n"));
+        assert_eq!(sector_rows(name, root), plain, "editing `{name}:` moved the sector");
+    }
+}
+
+#[test]
+fn tas_stays_a_minority() {
+    runtime::set_ruleset(Ruleset::load("t5", "").unwrap());
+    runtime::set_genre("normal");
+    let sector = astromapper_core::generate_sector("Bases".into(), "BASES".into(), 0.5).unwrap();
+    let systems = sector.systems();
+    let worlds: Vec<&World> = systems.iter().filter_map(|(_, _, v)| v.world.as_ref()).collect();
+    let share = |port: char| {
+        let at: Vec<&&World> = worlds.iter().filter(|w| w.starport == port).collect();
+        let tas = at.iter().filter(|w| w.bases.contains(&Base::TAS)).count();
+        tas as f64 / at.len().max(1) as f64
+    };
+    let (a, b, c) = (share('A'), share('B'), share('C'));
+    assert!(a < 0.35 && b < 0.2 && c < 0.1, "TAS shares A {a:.2}, B {b:.2}, C {c:.2}");
+    assert!(a > b && a > 0.0, "TAS favours the best ports");
+}